@@ -0,0 +1,27 @@
+//! The params which the benches are run on
+
+use hash40::{hash40, Hash40};
+use indexmap::IndexMap;
+use serde_prc::Value;
+
+/// A map with one param of each common kind, which `#[derive(Deserialize)]` structs can read
+pub fn entry(index: usize) -> Value {
+    let mut map = IndexMap::new();
+    map.insert(hash40("id"), Value::Hash(Hash40(index as u64)));
+    map.insert(hash40("level"), Value::I32(index as i32));
+    map.insert(hash40("scale"), Value::F32(index as f32 * 0.5));
+    map.insert(
+        hash40("name"),
+        Value::String(format!("entry_{}", index % 64)),
+    );
+    map.insert(
+        hash40("points"),
+        Value::List((0..4).map(|i| Value::U8(i as u8)).collect()),
+    );
+    Value::Map(map)
+}
+
+/// A single list of `count` maps
+pub fn wide(count: usize) -> Value {
+    Value::List((0..count).map(entry).collect())
+}
@@ -0,0 +1,62 @@
+//! Reading an in-memory file through `from_slice` tracks the position as a plain offset, while
+//! `from_reader` goes through `Read + Seek`. Compare the two on the same file. Each size reports
+//! its throughput in elements per second, which should stay flat as the size grows.
+
+mod common;
+
+use std::io::Cursor;
+
+use common::wide;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde::Deserialize;
+use serde_prc::Value;
+
+#[derive(Deserialize)]
+struct Entry {
+    id: u64,
+    level: i32,
+    scale: f32,
+    name: String,
+    points: Vec<u8>,
+}
+
+/// Touches every field so the struct reads aren't optimised out
+fn checksum(entries: &[Entry]) -> u64 {
+    entries.iter().fold(0, |sum, entry| {
+        sum ^ entry.id
+            ^ entry.level as u64
+            ^ entry.scale.to_bits() as u64
+            ^ entry.name.len() as u64
+            ^ entry.points.len() as u64
+    })
+}
+
+fn read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read");
+    for count in [1_000, 10_000, 100_000] {
+        let bytes = serde_prc::to_vec(&wide(count)).unwrap();
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::new("slice", count), &bytes, |b, bytes| {
+            b.iter(|| serde_prc::from_slice::<Value>(bytes).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("reader", count), &bytes, |b, bytes| {
+            b.iter(|| serde_prc::from_reader::<Value, _>(Cursor::new(bytes)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn read_structs(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_structs");
+    for count in [1_000, 10_000, 100_000] {
+        let bytes = serde_prc::to_vec(&wide(count)).unwrap();
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &bytes, |b, bytes| {
+            b.iter(|| checksum(&serde_prc::from_slice::<Vec<Entry>>(bytes).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, read, read_structs);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+//! Writing time should grow linearly with the number of params, both for wide files (many maps
+//! in one list) and for deeply nested ones. Each size reports its throughput in elements per
+//! second, which should stay flat as the size grows.
+
+mod common;
+
+use common::{entry, wide};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use hash40::hash40;
+use indexmap::IndexMap;
+use serde_prc::Value;
+
+/// `depth` maps, each holding the next one inside a list alongside a few siblings
+fn deep(depth: usize) -> Value {
+    (0..depth).fold(entry(0), |inner, level| {
+        let mut map = IndexMap::new();
+        map.insert(hash40("level"), Value::I32(level as i32));
+        map.insert(
+            hash40("children"),
+            Value::List(vec![entry(level), inner, entry(level + 1)]),
+        );
+        Value::Map(map)
+    })
+}
+
+fn write_wide(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write_wide");
+    for count in [1_000, 10_000, 100_000] {
+        let value = wide(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &value, |b, value| {
+            b.iter(|| serde_prc::to_vec(value).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn write_deep(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write_deep");
+    for depth in [100, 200, 400] {
+        let value = deep(depth);
+        group.throughput(Throughput::Elements(depth as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &value, |b, value| {
+            b.iter(|| serde_prc::to_vec(value).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, write_wide, write_deep);
+criterion_main!(benches);
@@ -0,0 +1,348 @@
+//! `prc`, a command line tool for inspecting, converting and editing param files.
+//!
+//! Params inside of a file are addressed by paths of map keys and list indices separated by
+//! `/`, such as `fighter_param_table/0/walk_speed`. Keys can be labels or `0x0123456789` hex
+//! literals. Values are read and printed in the format of [`serde_prc::text`].
+//!
+//! Exits with 0 on success, 1 when `diff` finds differences or `validate` finds a broken file
+//! and 2 on any other error.
+
+use std::{
+    error::Error,
+    fmt::Display,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use serde_prc::{
+    labels::Labels,
+    tagged::{Tagged, TaggedRef},
+    text, xml, Hash40, Value,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Inspects, converts and edits param (.prc) files
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// A `hash,label` CSV or newline separated list of labels to show hashes with. Can be given
+    /// more than once, in which case earlier files take precedence.
+    #[arg(short, long, global = true)]
+    labels: Vec<PathBuf>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints a param file as an indented tree
+    Dump { file: PathBuf },
+
+    /// Converts a param file to paramxml
+    ToXml {
+        file: PathBuf,
+        /// Where to write the result instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Converts paramxml to a param file
+    FromXml {
+        file: PathBuf,
+        /// Where to write the result instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Converts a param file to JSON which keeps the type of every param
+    ToJson {
+        file: PathBuf,
+        /// Where to write the result instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Converts JSON written by `to-json` to a param file
+    FromJson {
+        file: PathBuf,
+        /// Where to write the result instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Prints the param at a path
+    Get { file: PathBuf, path: String },
+
+    /// Replaces the param at a path, such as `set fighter_param.prc speed 1.5f`
+    Set {
+        file: PathBuf,
+        path: String,
+        value: String,
+        /// Where to write the result instead of overwriting the file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Allow the new value to have a different param type than the old one
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Lists the params which were changed, added or removed between two param files
+    Diff { a: PathBuf, b: PathBuf },
+
+    /// Checks that param files decode without errors
+    Validate {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    match run(args) {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: Args) -> Result<ExitCode> {
+    let mut labels = Labels::new();
+    for path in &args.labels {
+        let file = Labels::from_path(path).map_err(|error| context(path, error))?;
+        // Conflicts between files are expected, the earlier file just wins
+        for conflict in file.conflicts() {
+            eprintln!("warning: {}: {conflict}", path.display());
+        }
+        labels.merge(file);
+    }
+    labels.install();
+
+    match args.command {
+        Command::Dump { file } => {
+            let value = read_param(&file)?;
+            write_output(None, text::to_string_pretty(&value).as_bytes())?;
+        }
+        Command::ToXml { file, output } => {
+            let value = read_param(&file)?;
+            write_output(output.as_deref(), xml::to_string(&value).as_bytes())?;
+        }
+        Command::FromXml { file, output } => {
+            let value = xml::from_str(&read_text(&file)?).map_err(|error| context(&file, error))?;
+            write_output(output.as_deref(), &serde_prc::to_vec(&value)?)?;
+        }
+        Command::ToJson { file, output } => {
+            let value = read_param(&file)?;
+            let mut json = serde_json::to_string_pretty(&TaggedRef(&value))?;
+            json.push('\n');
+            write_output(output.as_deref(), json.as_bytes())?;
+        }
+        Command::FromJson { file, output } => {
+            let Tagged(value) =
+                serde_json::from_str(&read_text(&file)?).map_err(|error| context(&file, error))?;
+            write_output(output.as_deref(), &serde_prc::to_vec(&value)?)?;
+        }
+        Command::Get { file, path } => {
+            let value = read_param(&file)?;
+            let param = get(&value, &path)?;
+            write_output(None, text::to_string_pretty(param).as_bytes())?;
+        }
+        Command::Set {
+            file,
+            path,
+            value,
+            output,
+            force,
+        } => {
+            let new = text::from_str(&value).map_err(|error| format!("Invalid value: {error}"))?;
+            let mut root = read_param(&file)?;
+            let param = get_mut(&mut root, &path)?;
+
+            if !force && param.param_id() != new.param_id() {
+                return Err(format!(
+                    "{} is a {:?} param but the new value is a {:?} param (use --force to change it)",
+                    display_path(&path),
+                    param.param_id(),
+                    new.param_id()
+                )
+                .into());
+            }
+
+            *param = new;
+            let bytes = serde_prc::to_vec(&root)?;
+            let output = output.unwrap_or(file);
+            fs::write(&output, bytes).map_err(|error| context(&output, error))?;
+        }
+        Command::Diff { a, b } => {
+            let mut changes = Vec::new();
+            diff("", &read_param(&a)?, &read_param(&b)?, &mut changes);
+
+            let mut out = String::new();
+            for change in &changes {
+                out.push_str(change);
+                out.push('\n');
+            }
+            write_output(None, out.as_bytes())?;
+
+            if !changes.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Validate { files } => {
+            let mut code = ExitCode::SUCCESS;
+            for file in &files {
+                match read_param(file) {
+                    Ok(_) => println!("{}: ok", file.display()),
+                    Err(error) => {
+                        eprintln!("{error}");
+                        code = ExitCode::FAILURE;
+                    }
+                }
+            }
+            return Ok(code);
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Prefixes an error with the file it is about
+fn context(path: &Path, error: impl Display) -> Box<dyn Error> {
+    format!("{}: {error}", path.display()).into()
+}
+
+fn read_text(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|error| context(path, error))
+}
+
+fn read_param(path: &Path) -> Result<Value> {
+    let bytes = fs::read(path).map_err(|error| context(path, error))?;
+    serde_prc::from_slice(&bytes).map_err(|error| context(path, error))
+}
+
+fn write_output(output: Option<&Path>, bytes: &[u8]) -> Result<()> {
+    match output {
+        Some(path) => fs::write(path, bytes).map_err(|error| context(path, error)),
+        None => Ok(std::io::stdout().lock().write_all(bytes)?),
+    }
+}
+
+/// Splits a path into its keys and indices, ignoring leading, trailing and repeated slashes
+fn segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|segment| !segment.is_empty())
+}
+
+fn display_path(path: &str) -> String {
+    let path = segments(path).collect::<Vec<_>>().join("/");
+    format!("/{path}")
+}
+
+/// A map key as a path segment. Labels with a slash in them are written as hex literals, so that
+/// the path can be passed back to `get` and `set`.
+fn key_segment(key: Hash40) -> String {
+    let label = key.to_string();
+    if label.contains('/') {
+        format!("{:#012x}", key.0)
+    } else {
+        label
+    }
+}
+
+fn not_found(path: &str, segment: &str) -> Box<dyn Error> {
+    format!("{} has no param {segment:?}", display_path(path)).into()
+}
+
+fn parse_index(path: &str, segment: &str) -> Result<usize> {
+    segment.parse().map_err(|_| not_found(path, segment))
+}
+
+fn parse_key(path: &str, segment: &str) -> Result<Hash40> {
+    serde_prc::hash_from_label(segment).ok_or_else(|| not_found(path, segment))
+}
+
+fn leaf_error(path: &str, segment: &str, value: &Value) -> Box<dyn Error> {
+    format!(
+        "{} has no param {segment:?}: {:?} params have no children",
+        display_path(path),
+        value.param_id()
+    )
+    .into()
+}
+
+/// The param at `path` inside of `value`
+fn get<'v>(mut value: &'v Value, path: &str) -> Result<&'v Value> {
+    for segment in segments(path) {
+        value = match value {
+            Value::List(list) => list.get(parse_index(path, segment)?),
+            Value::Map(map) => map.get(&parse_key(path, segment)?),
+            _ => return Err(leaf_error(path, segment, value)),
+        }
+        .ok_or_else(|| not_found(path, segment))?;
+    }
+
+    Ok(value)
+}
+
+/// The param at `path` inside of `value`, for replacing it
+fn get_mut<'v>(mut value: &'v mut Value, path: &str) -> Result<&'v mut Value> {
+    for segment in segments(path) {
+        value = match value {
+            Value::List(list) => list.get_mut(parse_index(path, segment)?),
+            Value::Map(map) => map.get_mut(&parse_key(path, segment)?),
+            _ => return Err(leaf_error(path, segment, value)),
+        }
+        .ok_or_else(|| not_found(path, segment))?;
+    }
+
+    Ok(value)
+}
+
+/// Adds a line to `changes` for every param which differs between `a` and `b`.
+///
+/// Lists are compared by index and maps by key, so reordering the entries of a map is not a
+/// change. Leaf params are compared through their text form, which includes the param type and
+/// treats NaN as equal to itself.
+fn diff(path: &str, a: &Value, b: &Value, changes: &mut Vec<String>) {
+    let change = |kind: char, path: &str, value: &Value| {
+        format!("{kind} {}: {}", display_path(path), text::to_string(value))
+    };
+
+    match (a, b) {
+        (Value::List(a), Value::List(b)) => {
+            for (index, (a, b)) in a.iter().zip(b).enumerate() {
+                diff(&format!("{path}/{index}"), a, b, changes);
+            }
+            for (index, a) in a.iter().enumerate().skip(b.len()) {
+                changes.push(change('-', &format!("{path}/{index}"), a));
+            }
+            for (index, b) in b.iter().enumerate().skip(a.len()) {
+                changes.push(change('+', &format!("{path}/{index}"), b));
+            }
+        }
+        (Value::Map(a), Value::Map(b)) => {
+            for (key, a) in a {
+                let path = format!("{path}/{}", key_segment(*key));
+                match b.get(key) {
+                    Some(b) => diff(&path, a, b, changes),
+                    None => changes.push(change('-', &path, a)),
+                }
+            }
+            for (key, b) in b.iter().filter(|(key, _)| !a.contains_key(*key)) {
+                changes.push(change('+', &format!("{path}/{}", key_segment(*key)), b));
+            }
+        }
+        _ => {
+            let (a, b) = (text::to_string(a), text::to_string(b));
+            if a != b {
+                changes.push(format!("~ {}: {a} -> {b}", display_path(path)));
+            }
+        }
+    }
+}
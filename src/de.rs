@@ -2,13 +2,18 @@ use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
 use hash40::Hash40;
 use indexmap::IndexMap;
 use serde::{
-    de::{MapAccess, SeqAccess, Visitor},
+    de::{
+        value::{BorrowedStrDeserializer, StrDeserializer},
+        DeserializeSeed, EnumAccess, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+    },
     forward_to_deserialize_any, Deserialize, Deserializer,
 };
 use std::{
+    borrow::Cow,
     collections::HashMap,
     fmt::{Debug, Display},
     io::{Read, Seek, SeekFrom},
+    rc::Rc,
 };
 use thiserror::Error;
 
@@ -59,8 +64,27 @@ impl Display for Error {
 
 impl std::error::Error for Error {}
 
+impl Error {
+    /// The underlying cause of this error, without the parse position information
+    pub fn kind(&self) -> &ErrorKind {
+        &self.cause
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ErrorKind {
+    #[error("Invalid file magic {0:x?} (expected \"paracobn\")")]
+    BadMagic([u8; 8]),
+
+    #[error("File ends before the end of the header")]
+    TruncatedHeader,
+
+    #[error("Hash table size {0:#x} is not a multiple of 8")]
+    MisalignedHashTable(u32),
+
+    #[error("Reference data extends past the end of the file (start {start:#x}, size {size:#x})")]
+    ReferenceDataPastEnd { start: usize, size: usize },
+
     #[error("Invalid param id {0:#x}")]
     InvalidParamId(u8),
 
@@ -78,6 +102,20 @@ pub enum ErrorKind {
     )]
     MapRefOutOfBounds { start: usize, num_elements: usize },
 
+    #[error(
+        "Child param offset points out of bounds (parent at {parent:#x}, child at {child:#x})"
+    )]
+    ChildOutOfBounds { parent: u64, child: u64 },
+
+    #[error("Expected a {expected:?} param, found {found:?}")]
+    UnexpectedParam { expected: ParamId, found: ParamId },
+
+    #[error("Map has no key {0}")]
+    MissingKey(Hash40),
+
+    #[error("List index {index} is out of bounds (length {len})")]
+    IndexOutOfBounds { index: usize, len: usize },
+
     #[error(transparent)]
     IO(#[from] std::io::Error),
 
@@ -109,14 +147,17 @@ where
     }
 }
 
+/// Propagates an error, noting what was being parsed and where. The position is only looked up
+/// once something has gone wrong, so it is wherever the reader stopped.
 macro_rules! tri {
     ($reader:expr, $parsing:ident, $e:expr) => {{
-        let __position = $reader.stream_position().ok();
         let __result: Result<_, Error> = $e;
         match __result {
             Ok(__value) => __value,
             Err(mut __error) => {
-                __error.position_stack.push((ParseId::$parsing, __position));
+                __error
+                    .position_stack
+                    .push((ParseId::$parsing, $reader.position().ok()));
                 return Err(__error);
             }
         }
@@ -129,28 +170,58 @@ macro_rules! tri_map {
     };
 }
 
-pub(crate) struct ReferenceData {
+/// Controls how params are matched against the types they are deserialized into
+///
+/// ```
+/// # use serde_prc::de::Options;
+/// let bytes = serde_prc::to_vec(&vec![1u8, 2, 3]).unwrap();
+///
+/// // By default, a `u8` param can fill any integer which can hold it
+/// assert!(serde_prc::from_slice::<Vec<i32>>(&bytes).is_ok());
+///
+/// // Strict mode requires the exact param type
+/// assert!(serde_prc::from_slice_with::<Vec<i32>>(&bytes, Options::new().strict(true)).is_err());
+/// assert!(serde_prc::from_slice_with::<Vec<u8>>(&bytes, Options::new().strict(true)).is_ok());
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Options {
+    strict: bool,
+}
+
+impl Options {
+    pub const fn new() -> Self {
+        Self { strict: false }
+    }
+
+    /// When strict, primitives can only be deserialized from the param type with the same width
+    /// and signedness, erroring with [`ErrorKind::UnexpectedParam`] otherwise. `i64`, `u64` and
+    /// `f64` are matched against the params that the serializer writes them as by default: `i32`,
+    /// hashes and `f32`.
+    pub const fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+pub(crate) struct ReferenceData<'de> {
     file_offset: usize,
-    raw: Vec<u8>,
-    strings: HashMap<u32, String>,
-    maps: HashMap<u32, Vec<(Hash40, u32)>>,
+    raw: Cow<'de, [u8]>,
+    strings: HashMap<u32, usize>,
+    /// Struct tables by offset and length, since a table can be the start of a longer one or sit
+    /// at the same offset as an empty one
+    maps: HashMap<(u32, usize), Vec<(Hash40, u32)>>,
 }
 
-impl ReferenceData {
+impl<'de> ReferenceData<'de> {
     #[cfg(test)]
-    pub fn mock(bytes: &[u8]) -> Self {
-        Self {
-            file_offset: 0,
-            raw: bytes.to_vec(),
-            strings: HashMap::new(),
-            maps: HashMap::new(),
-        }
+    pub fn mock(bytes: &'de [u8]) -> Self {
+        Self::new(bytes, 0)
     }
 
-    pub fn new(bytes: Vec<u8>, file_offset: usize) -> Self {
+    pub fn new(bytes: impl Into<Cow<'de, [u8]>>, file_offset: usize) -> Self {
         Self {
             file_offset,
-            raw: bytes,
+            raw: bytes.into(),
             strings: HashMap::new(),
             maps: HashMap::new(),
         }
@@ -158,35 +229,381 @@ impl ReferenceData {
 
     #[cfg(test)]
     pub fn empty() -> Self {
-        Self {
-            file_offset: 0,
-            raw: vec![],
-            strings: HashMap::new(),
-            maps: HashMap::new(),
+        Self::new(&[][..], 0)
+    }
+
+    /// Reads the struct table at `offset`, returning each key alongside the absolute position
+    /// of its value given the position of the map param (`data_start`)
+    pub fn get_map(
+        &mut self,
+        hashes: &[Hash40],
+        offset: u32,
+        len: usize,
+        data_start: u64,
+    ) -> Result<Vec<(Hash40, u64)>, Error> {
+        if let Some(cached) = self.maps.get(&(offset, len)) {
+            return Ok(cached
+                .iter()
+                .map(|(hash, offset)| (*hash, data_start + *offset as u64))
+                .collect());
+        }
+
+        let offset = offset as usize;
+
+        if offset + len * 8 > self.raw.len() {
+            return Err(Error::from(ErrorKind::MapRefOutOfBounds {
+                start: self.file_offset + offset,
+                num_elements: len,
+            }));
         }
+
+        let mut fields = Vec::with_capacity(len);
+
+        for index in 0..len {
+            let local_hash_offset = offset + index * 8;
+            let local_data_offset = local_hash_offset + 4;
+            let hash_index =
+                LittleEndian::read_u32(&self.raw[local_hash_offset..local_data_offset]) as usize;
+            let data_offset =
+                LittleEndian::read_u32(&self.raw[local_data_offset..local_data_offset + 4]);
+
+            let Some(hash) = hashes.get(hash_index) else {
+                return Err(Error::from(ErrorKind::HashOutOfBounds(hash_index)));
+            };
+
+            fields.push((*hash, data_offset));
+        }
+
+        self.maps.insert((offset as u32, len), fields.clone());
+
+        Ok(fields
+            .into_iter()
+            .map(|(hash, offset)| (hash, data_start + offset as u64))
+            .collect())
     }
 }
 
-struct ParamFileReader<'a, R: Read + Seek> {
-    reference: ReferenceData,
-    hashes: &'a [Hash40],
+/// A string read out of the reference data, borrowed from the original input if possible
+enum Reference<'de, 's> {
+    Borrowed(&'de str),
+    Copied(&'s str),
+}
+
+/// The parsed header of a param file: the hash table and the raw reference data
+pub(crate) struct Header<'de> {
+    pub hashes: Vec<Hash40>,
+    pub reference: Cow<'de, [u8]>,
+}
+
+impl<'de> Header<'de> {
+    /// The absolute offset of the reference data within the file
+    pub fn reference_offset(&self) -> usize {
+        0x10 + self.hashes.len() * 8
+    }
+
+    /// The absolute offset of the root param within the file
+    pub fn body_offset(&self) -> usize {
+        self.reference_offset() + self.reference.len()
+    }
+
+    /// Reads everything up to the reference data, returning the hash table and the size of the
+    /// reference data
+    fn read_hashes<R: Read>(reader: &mut R) -> Result<(Vec<Hash40>, usize), Error> {
+        fn truncated(error: std::io::Error) -> Error {
+            if error.kind() == std::io::ErrorKind::UnexpectedEof {
+                Error::from(ErrorKind::TruncatedHeader)
+            } else {
+                Error::from(error)
+            }
+        }
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic).map_err(truncated)?;
+
+        if magic != *b"paracobn" {
+            return Err(Error::from(ErrorKind::BadMagic(magic)));
+        }
+
+        let hash_data_size = reader.read_u32::<LittleEndian>().map_err(truncated)?;
+        if hash_data_size % 8 != 0 {
+            return Err(Error::from(ErrorKind::MisalignedHashTable(hash_data_size)));
+        }
+
+        let ref_data_size = reader.read_u32::<LittleEndian>().map_err(truncated)?;
+
+        // The size comes from the file, so read through `take` instead of allocating up front
+        // in case it is bogus
+        let mut hash_data = Vec::new();
+        reader
+            .take(hash_data_size as u64)
+            .read_to_end(&mut hash_data)?;
+
+        if hash_data.len() != hash_data_size as usize {
+            return Err(Error::from(ErrorKind::TruncatedHeader));
+        }
+
+        let hashes = hash_data
+            .chunks_exact(8)
+            .map(|chunk| Hash40(LittleEndian::read_u64(chunk)))
+            .collect();
+
+        Ok((hashes, ref_data_size as usize))
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let (hashes, ref_data_size) = Self::read_hashes(reader)?;
+
+        let mut reference = Vec::new();
+        reader
+            .take(ref_data_size as u64)
+            .read_to_end(&mut reference)?;
+
+        if reference.len() != ref_data_size {
+            return Err(Error::from(ErrorKind::ReferenceDataPastEnd {
+                start: 0x10 + hashes.len() * 8,
+                size: ref_data_size,
+            }));
+        }
+
+        Ok(Self {
+            hashes,
+            reference: Cow::Owned(reference),
+        })
+    }
+
+    /// Parses the header of an in-memory file by indexing into it, borrowing the reference data
+    /// from it
+    pub fn parse(bytes: &'de [u8]) -> Result<Self, Error> {
+        let truncated = || Error::from(ErrorKind::TruncatedHeader);
+
+        let magic = bytes.get(..8).ok_or_else(truncated)?;
+        if magic != b"paracobn" {
+            let magic = magic.try_into().expect("should be 8 bytes");
+            return Err(Error::from(ErrorKind::BadMagic(magic)));
+        }
+
+        let sizes = bytes.get(8..0x10).ok_or_else(truncated)?;
+        let hash_data_size = LittleEndian::read_u32(&sizes[..4]);
+        if hash_data_size % 8 != 0 {
+            return Err(Error::from(ErrorKind::MisalignedHashTable(hash_data_size)));
+        }
+
+        let ref_data_size = LittleEndian::read_u32(&sizes[4..]) as usize;
+
+        // Slicing off the start first means the sizes from the file are never added to anything
+        let hashes = bytes[0x10..]
+            .get(..hash_data_size as usize)
+            .ok_or_else(truncated)?
+            .chunks_exact(8)
+            .map(|chunk| Hash40(LittleEndian::read_u64(chunk)))
+            .collect::<Vec<_>>();
+
+        let start = 0x10 + hash_data_size as usize;
+        let Some(reference) = bytes[start..].get(..ref_data_size) else {
+            return Err(Error::from(ErrorKind::ReferenceDataPastEnd {
+                start,
+                size: ref_data_size,
+            }));
+        };
+
+        Ok(Self {
+            hashes,
+            reference: Cow::Borrowed(reference),
+        })
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Where the body of a param file is read from: either [`IoRead`] for any `Read + Seek` source,
+/// or [`SliceRead`] for a file which is already in memory
+pub trait Input: Read + private::Sealed {
+    /// The absolute position in the file
+    fn position(&mut self) -> std::io::Result<u64>;
+
+    /// Moves to an absolute position in the file
+    fn seek_to(&mut self, position: u64) -> std::io::Result<()>;
+
+    /// The absolute position of the end of the file
+    fn end(&mut self) -> std::io::Result<u64>;
+}
+
+/// Reads the body of a param file from a `Read + Seek` source
+pub struct IoRead<'a, R> {
     reader: &'a mut R,
-    peeked_param_id: Option<ParamId>,
 }
 
-impl<'a, R: Read + Seek> Read for ParamFileReader<'a, R> {
+impl<R: Read> Read for IoRead<'_, R> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         self.reader.read(buf)
     }
 }
 
-impl<'a, R: Read + Seek> Seek for ParamFileReader<'a, R> {
-    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
-        self.reader.seek(pos)
+impl<R: Read + Seek> private::Sealed for IoRead<'_, R> {}
+
+impl<R: Read + Seek> Input for IoRead<'_, R> {
+    fn position(&mut self) -> std::io::Result<u64> {
+        self.reader.stream_position()
+    }
+
+    fn seek_to(&mut self, position: u64) -> std::io::Result<()> {
+        self.reader.seek(SeekFrom::Start(position)).map(|_| ())
+    }
+
+    fn end(&mut self) -> std::io::Result<u64> {
+        let position = self.reader.stream_position()?;
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        self.reader.seek(SeekFrom::Start(position))?;
+        Ok(end)
+    }
+}
+
+/// Reads the body of an in-memory param file, keeping track of the position as a plain offset
+pub struct SliceRead<'de> {
+    bytes: &'de [u8],
+    position: usize,
+}
+
+impl Read for SliceRead<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.bytes.get(self.position..).unwrap_or_default();
+        let len = remaining.len().min(buf.len());
+        buf[..len].copy_from_slice(&remaining[..len]);
+        self.position += len;
+        Ok(len)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        let end = self.position.saturating_add(buf.len());
+        let Some(bytes) = self.bytes.get(self.position..end) else {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        };
+
+        buf.copy_from_slice(bytes);
+        self.position = end;
+        Ok(())
+    }
+}
+
+impl private::Sealed for SliceRead<'_> {}
+
+impl Input for SliceRead<'_> {
+    fn position(&mut self) -> std::io::Result<u64> {
+        Ok(self.position as u64)
+    }
+
+    fn seek_to(&mut self, position: u64) -> std::io::Result<()> {
+        // Seeking past the end is fine, reading from there fails instead
+        self.position = usize::try_from(position).unwrap_or(usize::MAX);
+        Ok(())
+    }
+
+    fn end(&mut self) -> std::io::Result<u64> {
+        Ok(self.bytes.len() as u64)
+    }
+}
+
+struct ParamFileReader<'de, 'a, R: Input> {
+    reference: ReferenceData<'de>,
+    hashes: &'a [Hash40],
+    reader: R,
+    peeked_param_id: Option<ParamId>,
+}
+
+impl<'de, 'a, R: Input> Read for ParamFileReader<'de, 'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.reader.read(buf)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+        self.reader.read_exact(buf)
     }
 }
 
-impl<'a, R: Read + Seek> ParamFileReader<'a, R> {
+impl<'de, 'a, R: Input> ParamFileReader<'de, 'a, R> {
+    fn position(&mut self) -> std::io::Result<u64> {
+        self.reader.position()
+    }
+
+    fn seek_to(&mut self, position: u64) -> std::io::Result<()> {
+        self.peeked_param_id = None;
+        self.reader.seek_to(position)
+    }
+
+    /// Checks that a child param starts after the first `header` bytes of its parent and before
+    /// the end of the file. Skipping relies on this to always move forward, so an offset
+    /// pointing back at the parent can't send it around in circles.
+    fn check_child(&mut self, parent: u64, header: u64, child: u64) -> Result<(), Error> {
+        if child < parent.saturating_add(header) || child >= self.reader.end()? {
+            return Err(Error::from(ErrorKind::ChildOutOfBounds { parent, child }));
+        }
+
+        Ok(())
+    }
+
+    /// Moves past the next param without decoding it.
+    ///
+    /// Children are always written after their parent and in order, so a list or map ends where
+    /// its last child does. Only the last child of each level is looked at, and maps find theirs
+    /// through the struct table.
+    fn skip_value(&mut self) -> Result<(), Error> {
+        loop {
+            let param_id = self.next_param_id()?;
+            let base_position = tri_map!(self.reader, ParamId, self.reader.position()) - 1;
+
+            let size = match param_id {
+                ParamId::Bool | ParamId::I8 | ParamId::U8 => 1,
+                ParamId::I16 | ParamId::U16 => 2,
+                ParamId::I32 | ParamId::U32 | ParamId::F32 | ParamId::Hash | ParamId::String => 4,
+                ParamId::List => {
+                    let num_elements = tri_map!(self.reader, List, self.read_u32::<LittleEndian>());
+                    let Some(last) = num_elements.checked_sub(1) else {
+                        return Ok(());
+                    };
+
+                    tri_map!(
+                        self.reader,
+                        List,
+                        self.seek_to(base_position + 5 + last as u64 * 4)
+                    );
+                    let offset = tri_map!(self.reader, List, self.read_u32::<LittleEndian>());
+                    let child = base_position + offset as u64;
+                    tri!(
+                        self.reader,
+                        List,
+                        self.check_child(base_position, 5 + num_elements as u64 * 4, child)
+                    );
+                    tri_map!(self.reader, List, self.seek_to(child));
+                    continue;
+                }
+                ParamId::Map => {
+                    let num_elements =
+                        tri_map!(self.reader, Map, self.read_u32::<LittleEndian>()) as usize;
+                    let ref_position = tri_map!(self.reader, Map, self.read_u32::<LittleEndian>());
+                    let keys = tri!(
+                        self.reader,
+                        Map,
+                        self.get_map(ref_position, num_elements, base_position)
+                    );
+                    let Some(last) = keys.into_iter().map(|(_, offset)| offset).max() else {
+                        return Ok(());
+                    };
+
+                    tri!(self.reader, Map, self.check_child(base_position, 9, last));
+                    tri_map!(self.reader, Map, self.seek_to(last));
+                    continue;
+                }
+            };
+
+            // Read the data instead of seeking over it so a truncated file still errors
+            let mut data = [0; 4];
+            tri_map!(self.reader, ParamId, self.read_exact(&mut data[..size]));
+            return Ok(());
+        }
+    }
+
     fn read_param_id(&mut self) -> Result<ParamId, Error> {
         let param_id = tri_map!(self.reader, ParamId, self.reader.read_u8());
         Ok(tri_map!(
@@ -214,35 +631,55 @@ impl<'a, R: Read + Seek> ParamFileReader<'a, R> {
         }
     }
 
-    fn get_string(&mut self, offset: u32) -> Result<String, Error> {
-        if let Some(cached) = self.reference.strings.get(&offset) {
-            return Ok(cached.clone());
-        }
+    fn get_string<'s>(&'s mut self, offset: u32) -> Result<Reference<'de, 's>, Error> {
+        let start = offset as usize;
 
-        let offset = offset as usize;
+        let len = match self.reference.strings.get(&offset) {
+            Some(len) => *len,
+            None => {
+                if start >= self.reference.raw.len() {
+                    return Err(Error::from(ErrorKind::StringRefOutOfBounds(start)));
+                }
 
-        if offset >= self.reference.raw.len() {
-            return Err(Error::from(ErrorKind::StringRefOutOfBounds(offset)));
-        }
-
-        let data = &self.reference.raw[offset..];
-        let len =
-            data.iter()
-                .position(|byte| *byte == b'\0')
-                .ok_or(ErrorKind::StringRefOutOfBounds(
-                    self.reference.file_offset + offset,
-                ))?;
-        let string = &data[..len];
-        if let Some(pos) = string.iter().position(|byte| !byte.is_ascii()) {
-            return Err(Error::from(ErrorKind::StringNotAscii(
-                self.reference.file_offset + offset + pos,
-            )));
-        }
+                let data = &self.reference.raw[start..];
+                let len = data.iter().position(|byte| *byte == b'\0').ok_or(
+                    ErrorKind::StringRefOutOfBounds(self.reference.file_offset + start),
+                )?;
+                if let Some(pos) = data[..len].iter().position(|byte| !byte.is_ascii()) {
+                    return Err(Error::from(ErrorKind::StringNotAscii(
+                        self.reference.file_offset + start + pos,
+                    )));
+                }
+
+                self.reference.strings.insert(offset, len);
+                len
+            }
+        };
 
-        // SAFETY: We check that all chars are non-zero and ascii above
-        let string = unsafe { std::str::from_utf8_unchecked(string).to_string() };
-        self.reference.strings.insert(offset as u32, string.clone());
-        Ok(string)
+        // SAFETY: We check that all chars are non-zero and ascii above, or did so when the
+        // length was cached
+        Ok(match &self.reference.raw {
+            Cow::Borrowed(raw) => Reference::Borrowed(unsafe {
+                std::str::from_utf8_unchecked(&raw[start..start + len])
+            }),
+            Cow::Owned(raw) => Reference::Copied(unsafe {
+                std::str::from_utf8_unchecked(&raw[start..start + len])
+            }),
+        })
+    }
+
+    /// Reads the body of a hash param, looking the index up in the hash table
+    fn read_hash(&mut self) -> Result<Hash40, Error> {
+        let index = tri_map!(self.reader, Hash, self.reader.read_u32::<LittleEndian>()) as usize;
+
+        let Some(hash) = self.hashes.get(index).copied() else {
+            return Err(Error {
+                cause: ErrorKind::HashOutOfBounds(index),
+                position_stack: vec![(ParseId::Hash, self.reader.position().ok())],
+            });
+        };
+
+        Ok(hash)
     }
 
     fn get_map(
@@ -251,61 +688,51 @@ impl<'a, R: Read + Seek> ParamFileReader<'a, R> {
         len: usize,
         data_start: u64,
     ) -> Result<Vec<(Hash40, u64)>, Error> {
-        if let Some(cached) = self.reference.maps.get(&offset) {
-            return Ok(cached
-                .iter()
-                .map(|(hash, offset)| (*hash, data_start + *offset as u64))
-                .collect());
-        }
-
-        let offset = offset as usize;
-
-        if offset + len * 8 > self.reference.raw.len() {
-            return Err(Error::from(ErrorKind::MapRefOutOfBounds {
-                start: self.reference.file_offset + offset,
-                num_elements: len,
-            }));
-        }
-
-        let mut fields = Vec::with_capacity(len as usize);
-
-        for index in 0..len {
-            let local_hash_offset = offset + index * 8;
-            let local_data_offset = local_hash_offset + 4;
-            let hash_index =
-                LittleEndian::read_u32(&self.reference.raw[local_hash_offset..local_data_offset])
-                    as usize;
-            let data_offset = LittleEndian::read_u32(
-                &self.reference.raw[local_data_offset..local_data_offset + 4],
-            );
-
-            let Some(hash) = self.hashes.get(hash_index) else {
-                return Err(Error::from(ErrorKind::HashOutOfBounds(hash_index)));
-            };
+        self.reference.get_map(self.hashes, offset, len, data_start)
+    }
+}
 
-            fields.push((*hash, data_offset));
-        }
+/// Maps the hashes of a struct's fields back to their names
+type FieldTable = HashMap<Hash40, &'static str>;
 
-        self.reference.maps.insert(offset as u32, fields.clone());
+/// The field tables of every struct type deserialized so far. Serde hands out the same `fields`
+/// slice for every instance of a struct, so its address and length identify the struct type and
+/// each name only has to be hashed once.
+#[derive(Default)]
+struct FieldTables {
+    tables: HashMap<(usize, usize), Rc<FieldTable>>,
+}
 
-        Ok(fields
-            .into_iter()
-            .map(|(hash, offset)| (hash, data_start + offset as u64))
-            .collect())
+impl FieldTables {
+    fn get(&mut self, fields: &'static [&'static str]) -> Rc<FieldTable> {
+        let key = (fields.as_ptr() as usize, fields.len());
+        self.tables
+            .entry(key)
+            .or_insert_with(|| {
+                let mut table = FieldTable::with_capacity(fields.len());
+                for field in fields {
+                    // Keep the first field when two names collide
+                    table.entry(hash40::hash40(field)).or_insert(field);
+                }
+                Rc::new(table)
+            })
+            .clone()
     }
 }
 
-pub struct ValueDeserializer<'a, R: Read + Seek> {
-    reader: ParamFileReader<'a, R>,
+pub struct ValueDeserializer<'de, 'a, R: Input> {
+    reader: ParamFileReader<'de, 'a, R>,
+    options: Options,
+    fields: FieldTables,
 }
 
-pub struct ListDeserializer<'a: 'b, 'b, R: Read + Seek> {
+pub struct ListDeserializer<'de, 'a: 'b, 'b, R: Input> {
     offsets: Vec<u64>,
     current: usize,
-    value_deserializer: &'b mut ValueDeserializer<'a, R>,
+    value_deserializer: &'b mut ValueDeserializer<'de, 'a, R>,
 }
 
-impl<'de, 'a: 'b, 'b, R: Read + Seek + 'de> SeqAccess<'de> for &mut ListDeserializer<'a, 'b, R> {
+impl<'de, 'a: 'b, 'b, R: Input> SeqAccess<'de> for &mut ListDeserializer<'de, 'a, 'b, R> {
     type Error = Error;
 
     fn size_hint(&self) -> Option<usize> {
@@ -318,12 +745,10 @@ impl<'de, 'a: 'b, 'b, R: Read + Seek + 'de> SeqAccess<'de> for &mut ListDeserial
     {
         match self.offsets.get(self.current) {
             Some(offset) => {
-                let _ = tri_map!(
+                tri_map!(
                     self.value_deserializer.reader,
                     ParamId,
-                    self.value_deserializer
-                        .reader
-                        .seek(SeekFrom::Start(*offset))
+                    self.value_deserializer.reader.seek_to(*offset)
                 );
 
                 self.current += 1;
@@ -340,15 +765,15 @@ impl<'de, 'a: 'b, 'b, R: Read + Seek + 'de> SeqAccess<'de> for &mut ListDeserial
     }
 }
 
-pub struct MapDeserializer<'a: 'b, 'b, R: Read + Seek> {
+pub struct MapDeserializer<'de, 'a: 'b, 'b, R: Input> {
     keys: Vec<(Hash40, u64)>,
     current: usize,
     current_key: usize,
-    fields: Option<&'static [&'static str]>,
-    value_deserializer: &'b mut ValueDeserializer<'a, R>,
+    fields: Option<Rc<FieldTable>>,
+    value_deserializer: &'b mut ValueDeserializer<'de, 'a, R>,
 }
 
-impl<'de, 'a: 'b, 'b, R: Read + Seek + 'de> MapAccess<'de> for &mut MapDeserializer<'a, 'b, R> {
+impl<'de, 'a: 'b, 'b, R: Input> MapAccess<'de> for &mut MapDeserializer<'de, 'a, 'b, R> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
@@ -362,9 +787,10 @@ impl<'de, 'a: 'b, 'b, R: Read + Seek + 'de> MapAccess<'de> for &mut MapDeseriali
         let key = self.keys[self.current].0;
         let map_key = if let Some(field) = self
             .fields
-            .and_then(|fields| fields.iter().find(|field| hash40::hash40(*field) == key))
+            .as_ref()
+            .and_then(|fields| fields.get(&key).copied())
         {
-            MapKeyDeserializer::Member(*field)
+            MapKeyDeserializer::Member(field)
         } else {
             MapKeyDeserializer::Hash(key)
         };
@@ -396,7 +822,7 @@ impl<'de, 'a: 'b, 'b, R: Read + Seek + 'de> MapAccess<'de> for &mut MapDeseriali
         tri_map!(
             self.value_deserializer.reader,
             Map,
-            self.value_deserializer.reader.seek(SeekFrom::Start(offset))
+            self.value_deserializer.reader.seek_to(offset)
         );
 
         let result = tri!(
@@ -411,12 +837,31 @@ impl<'de, 'a: 'b, 'b, R: Read + Seek + 'de> MapAccess<'de> for &mut MapDeseriali
     }
 }
 
-impl<'a, R: Read + Seek> ValueDeserializer<'a, R> {
+impl<'de, 'a, R: Read + Seek> ValueDeserializer<'de, 'a, IoRead<'a, R>> {
+    /// Deserializes the body of a file from `reader`, starting at its current position
     pub(crate) fn new(
-        reference_data: ReferenceData,
+        reference_data: ReferenceData<'de>,
         hashes: &'a [Hash40],
         reader: &'a mut R,
     ) -> Self {
+        Self::with_input(reference_data, hashes, IoRead { reader })
+    }
+}
+
+impl<'de, 'a> ValueDeserializer<'de, 'a, SliceRead<'de>> {
+    /// Deserializes the body of an in-memory file, starting at `position`
+    pub(crate) fn from_slice(
+        reference_data: ReferenceData<'de>,
+        hashes: &'a [Hash40],
+        bytes: &'de [u8],
+        position: usize,
+    ) -> Self {
+        Self::with_input(reference_data, hashes, SliceRead { bytes, position })
+    }
+}
+
+impl<'de, 'a, R: Input> ValueDeserializer<'de, 'a, R> {
+    fn with_input(reference_data: ReferenceData<'de>, hashes: &'a [Hash40], reader: R) -> Self {
         Self {
             reader: ParamFileReader {
                 reference: reference_data,
@@ -424,20 +869,42 @@ impl<'a, R: Read + Seek> ValueDeserializer<'a, R> {
                 reader,
                 peeked_param_id: None,
             },
+            options: Options::default(),
+            fields: FieldTables::default(),
+        }
+    }
+
+    pub(crate) fn with_options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// In strict mode, errors if the next param isn't `expected`
+    fn check_param(&mut self, expected: ParamId) -> Result<(), Error> {
+        if !self.options.strict {
+            return Ok(());
+        }
+
+        let found = self.reader.peek_param_id()?;
+        if found != expected {
+            tri!(
+                self.reader,
+                ParamId,
+                Err::<(), _>(Error::from(ErrorKind::UnexpectedParam { expected, found }))
+            );
         }
+
+        Ok(())
     }
 
-    fn deserialize_map<'de, V: Visitor<'de>>(
+    fn deserialize_map<V: Visitor<'de>>(
         &mut self,
         fields: Option<&'static [&'static str]>,
         visitor: V,
-    ) -> Result<V::Value, Error>
-    where
-        R: 'de,
-    {
+    ) -> Result<V::Value, Error> {
         // Subtract 1 from the current position to get the base offset all of the elemenets
         // are relative to
-        let base_position = tri_map!(self.reader, Map, self.reader.stream_position())
+        let base_position = tri_map!(self.reader, Map, self.reader.position())
             .checked_sub(1)
             .unwrap();
 
@@ -453,6 +920,7 @@ impl<'a, R: Read + Seek> ValueDeserializer<'a, R> {
                 .get_map(ref_position, num_elements, base_position)
         );
 
+        let fields = fields.map(|fields| self.fields.get(fields));
         let mut map_deserializer = MapDeserializer {
             keys,
             current: 0,
@@ -463,28 +931,56 @@ impl<'a, R: Read + Seek> ValueDeserializer<'a, R> {
 
         let result = visitor.visit_map(&mut map_deserializer);
 
-        // If the map deserializer finishes prematurely, we need to parse the last value
+        // If the map deserializer finishes prematurely, we need to skip the last value
         // so that we can advance to the correct cursor position
         if map_deserializer.current < num_elements {
             let offset = map_deserializer.keys.last().unwrap().1;
-            tri_map!(self.reader, Map, self.reader.seek(SeekFrom::Start(offset)));
-            tri!(self.reader, Map, Value::deserialize(&mut *self));
+            tri_map!(self.reader, Map, self.reader.seek_to(offset));
+            tri!(self.reader, Map, self.reader.skip_value());
         }
 
         Ok(tri!(self.reader, Map, result))
     }
 }
 
-impl<'de, 'a, R: Read + Seek + 'de> Deserializer<'de> for &mut ValueDeserializer<'a, R> {
+/// Deserializes primitives through `deserialize_any` after checking the param type in strict mode
+macro_rules! deserialize_primitives {
+    ($($method:ident => $param:ident),*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.check_param(ParamId::$param)?;
+                self.deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a, R: Input> Deserializer<'de> for &mut ValueDeserializer<'de, 'a, R> {
     type Error = Error;
 
+    deserialize_primitives! {
+        deserialize_bool => Bool,
+        deserialize_i8 => I8,
+        deserialize_u8 => U8,
+        deserialize_i16 => I16,
+        deserialize_u16 => U16,
+        deserialize_i32 => I32,
+        deserialize_u32 => U32,
+        deserialize_i64 => I32,
+        deserialize_u64 => Hash,
+        deserialize_f32 => F32,
+        deserialize_f64 => F32
+    }
+
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: serde::de::Visitor<'de>,
     {
         use ParamId as P;
 
-        let offset = self.reader.stream_position()?;
         let next = self.reader.next_param_id()?;
 
         match next {
@@ -521,31 +1017,24 @@ impl<'de, 'a, R: Read + Seek + 'de> Deserializer<'de> for &mut ValueDeserializer
                 Ok(tri!(self.reader, F32, visitor.visit_f32(value)))
             }
             P::Hash => {
-                let index =
-                    tri_map!(self.reader, Hash, self.reader.read_u32::<LittleEndian>()) as usize;
-
-                let position = self.reader.stream_position().ok();
-                let Some(hash) = self.reader.hashes.get(index).copied() else {
-                    return Err(Error {
-                        cause: ErrorKind::HashOutOfBounds(index),
-                        position_stack: vec![(ParseId::Hash, position)],
-                    });
-                };
-
+                let hash = self.reader.read_hash()?;
                 Ok(tri!(self.reader, Hash, visitor.visit_u64(hash.0)))
             }
             P::String => {
                 let ref_offset =
                     tri_map!(self.reader, String, self.reader.read_u32::<LittleEndian>());
 
-                let string = tri!(self.reader, String, self.reader.get_string(ref_offset));
+                let result = match tri!(self.reader, String, self.reader.get_string(ref_offset)) {
+                    Reference::Borrowed(string) => visitor.visit_borrowed_str(string),
+                    Reference::Copied(string) => visitor.visit_str(string),
+                };
 
-                Ok(tri!(self.reader, String, visitor.visit_string(string)))
+                Ok(tri!(self.reader, String, result))
             }
             P::List => {
                 // Subtract 1 from the current position to get the base offset all of the elemenets
                 // are relative to
-                let base_position = tri_map!(self.reader, List, self.reader.stream_position())
+                let base_position = tri_map!(self.reader, List, self.reader.position())
                     .checked_sub(1)
                     .unwrap();
 
@@ -568,12 +1057,12 @@ impl<'de, 'a, R: Read + Seek + 'de> Deserializer<'de> for &mut ValueDeserializer
 
                 let value = visitor.visit_seq(&mut list_deserializer);
 
-                // If the list deserializer finishes prematurely, we need to parse the last value
+                // If the list deserializer finishes prematurely, we need to skip the last value
                 // so that we can advance to the correct cursor position
                 if list_deserializer.current < list_deserializer.offsets.len() {
                     let offset = *list_deserializer.offsets.last().unwrap();
-                    tri_map!(self.reader, List, self.reader.seek(SeekFrom::Start(offset)));
-                    tri!(self.reader, List, Value::deserialize(&mut *self));
+                    tri_map!(self.reader, List, self.reader.seek_to(offset));
+                    tri!(self.reader, List, self.reader.skip_value());
                 }
 
                 Ok(tri!(self.reader, List, value))
@@ -595,16 +1084,7 @@ impl<'de, 'a, R: Read + Seek + 'de> Deserializer<'de> for &mut ValueDeserializer
     {
         if self.reader.peek_param_id()? == ParamId::Hash {
             let _ = self.reader.next_param_id();
-            let index =
-                tri_map!(self.reader, Hash, self.reader.read_u32::<LittleEndian>()) as usize;
-
-            let position = self.reader.stream_position().ok();
-            let Some(hash) = self.reader.hashes.get(index).copied() else {
-                return Err(Error {
-                    cause: ErrorKind::HashOutOfBounds(index),
-                    position_stack: vec![(ParseId::Hash, position)],
-                });
-            };
+            let hash = self.reader.read_hash()?;
 
             Ok(tri!(
                 self.reader,
@@ -633,14 +1113,296 @@ impl<'de, 'a, R: Read + Seek + 'de> Deserializer<'de> for &mut ValueDeserializer
         }
     }
 
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // Params can't be null, absent fields are handled by the map visitor
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        // Unit variants are stored as the variant name, either as a string or as a hash, and
+        // variants with data are stored as a map with a single entry keyed by the variant name
+        match self.reader.peek_param_id()? {
+            ParamId::String => {
+                let _ = self.reader.next_param_id();
+                let ref_offset =
+                    tri_map!(self.reader, String, self.reader.read_u32::<LittleEndian>());
+
+                let variant = match tri!(self.reader, String, self.reader.get_string(ref_offset)) {
+                    Reference::Borrowed(string) => Cow::Borrowed(string),
+                    Reference::Copied(string) => Cow::Owned(string.to_string()),
+                };
+
+                Ok(tri!(
+                    self.reader,
+                    String,
+                    visitor.visit_enum(EnumDeserializer {
+                        variant,
+                        payload: None,
+                        value_deserializer: self,
+                    })
+                ))
+            }
+            ParamId::Hash => {
+                let _ = self.reader.next_param_id();
+                let hash = self.reader.read_hash()?;
+
+                Ok(tri!(
+                    self.reader,
+                    Hash,
+                    visitor.visit_enum(EnumDeserializer {
+                        variant: variant_name(variants, hash),
+                        payload: None,
+                        value_deserializer: self,
+                    })
+                ))
+            }
+            ParamId::Map => {
+                let _ = self.reader.next_param_id();
+                let base_position = tri_map!(self.reader, Map, self.reader.position())
+                    .checked_sub(1)
+                    .unwrap();
+
+                let num_elements =
+                    tri_map!(self.reader, Map, self.reader.read_u32::<LittleEndian>()) as usize;
+
+                if num_elements != 1 {
+                    tri!(
+                        self.reader,
+                        Map,
+                        Err::<(), _>(serde::de::Error::invalid_length(
+                            num_elements,
+                            &"a map with a single entry"
+                        ))
+                    );
+                }
+
+                let ref_position =
+                    tri_map!(self.reader, Map, self.reader.read_u32::<LittleEndian>());
+                let (hash, offset) = tri!(
+                    self.reader,
+                    Map,
+                    self.reader
+                        .get_map(ref_position, num_elements, base_position)
+                )[0];
+
+                Ok(tri!(
+                    self.reader,
+                    Map,
+                    visitor.visit_enum(EnumDeserializer {
+                        variant: variant_name(variants, hash),
+                        payload: Some(offset),
+                        value_deserializer: self,
+                    })
+                ))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.reader.skip_value()?;
+        visitor.visit_unit()
+    }
+
+    // Hashes have their own param type, so strings never stand in for them
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
     forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char
-        bytes byte_buf option unit unit_struct newtype_struct seq tuple
-        tuple_struct map enum identifier ignored_any
+        i128 u128 char bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map identifier
+    }
+}
+
+/// Finds the variant whose name hashes to `hash`, falling back on the label of the hash so that
+/// the visitor can report it as an unknown variant
+fn variant_name<'de>(variants: &'static [&'static str], hash: Hash40) -> Cow<'de, str> {
+    match variants
+        .iter()
+        .find(|variant| hash40::hash40(variant) == hash)
+    {
+        Some(variant) => Cow::Borrowed(variant),
+        None => Cow::Owned(format!("{hash}")),
+    }
+}
+
+pub struct EnumDeserializer<'de, 'a: 'b, 'b, R: Input> {
+    variant: Cow<'de, str>,
+    payload: Option<u64>,
+    value_deserializer: &'b mut ValueDeserializer<'de, 'a, R>,
+}
+
+impl<'de, 'a: 'b, 'b, R: Input> EnumAccess<'de> for EnumDeserializer<'de, 'a, 'b, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let variant = match &self.variant {
+            &Cow::Borrowed(variant) => {
+                seed.deserialize(BorrowedStrDeserializer::<Error>::new(variant))?
+            }
+            Cow::Owned(variant) => seed.deserialize(StrDeserializer::<Error>::new(variant))?,
+        };
+
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a: 'b, 'b, R: Input> EnumDeserializer<'de, 'a, 'b, R> {
+    /// Moves the reader to the variant's data, erroring if this is a unit variant
+    fn seek_payload(&mut self, expected: &'static str) -> Result<(), Error> {
+        let Some(offset) = self.payload else {
+            return Err(<Error as serde::de::Error>::invalid_type(
+                Unexpected::UnitVariant,
+                &expected,
+            ));
+        };
+
+        tri_map!(
+            self.value_deserializer.reader,
+            Map,
+            self.value_deserializer.reader.seek_to(offset)
+        );
+
+        Ok(())
+    }
+}
+
+impl<'de, 'a: 'b, 'b, R: Input> VariantAccess<'de> for EnumDeserializer<'de, 'a, 'b, R> {
+    type Error = Error;
+
+    fn unit_variant(mut self) -> Result<(), Self::Error> {
+        // A unit variant written as a map still has a value, skip over it
+        if self.payload.is_some() {
+            self.seek_payload("unit variant")?;
+            self.value_deserializer.reader.skip_value()?;
+        }
+
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        self.seek_payload("newtype variant")?;
+        seed.deserialize(&mut *self.value_deserializer)
+    }
+
+    fn tuple_variant<V>(mut self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.seek_payload("tuple variant")?;
+        self.value_deserializer.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V>(
+        mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.seek_payload("struct variant")?;
+        self.value_deserializer
+            .deserialize_struct("", fields, visitor)
+    }
+}
+
+/// How strings are told apart from hashes when visiting a [`Value`]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Strings {
+    /// `0x0123456789` hex literals are hashes and one leading `\` is stripped from any other
+    /// string. This undoes the escaping [`ser::Escaped`](crate::ser::Escaped) does.
+    Escaped,
+
+    /// `0x0123456789` hex literals are hashes and every other string is kept as is
+    #[default]
+    Unescaped,
+
+    /// Every string is a string. Used for formats with a separate hash type, like param files.
+    Verbatim,
+}
+
+/// Builds a [`Value`] out of anything a deserializer has to offer.
+///
+/// Human readable formats write hashes and strings the same way. `Value`'s `Deserialize` impl
+/// uses [`Strings::Unescaped`] for them and [`Strings::Verbatim`] for the rest, so a string which
+/// looks like a `0x0123456789` hex literal is read back as a hash. Data written through
+/// [`ser::Escaped`](crate::ser::Escaped) keeps the two apart, use this as a seed with
+/// [`Strings::Escaped`] to read it:
+///
+/// ```
+/// # use serde::de::DeserializeSeed;
+/// # use serde_prc::{de::{Strings, ValueVisitor}, ser::Escaped, Value};
+/// let value = Value::List(vec![Value::String("0x0123456789".into())]);
+/// let json = serde_json::to_string(&Escaped(&value)).unwrap();
+///
+/// let mut deserializer = serde_json::Deserializer::from_str(&json);
+/// let read = ValueVisitor::new()
+///     .strings(Strings::Escaped)
+///     .deserialize(&mut deserializer)
+///     .unwrap();
+/// assert_eq!(read, value);
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct ValueVisitor {
+    strings: Strings,
+}
+
+impl ValueVisitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how strings are told apart from hashes
+    pub fn strings(mut self, strings: Strings) -> Self {
+        self.strings = strings;
+        self
     }
 }
 
-struct ValueVisitor;
+impl<'de> DeserializeSeed<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
 
 impl<'de> Visitor<'de> for ValueVisitor {
     type Value = Value;
@@ -719,13 +1481,19 @@ impl<'de> Visitor<'de> for ValueVisitor {
     where
         E: serde::de::Error,
     {
-        if v.starts_with("0x") && v.len() == "0x123456789A".len() {
-            match u64::from_str_radix(v.strip_prefix("0x").unwrap(), 16) {
-                Ok(v) => Ok(Value::Hash(Hash40(v))),
-                Err(_) => Ok(Value::String(v.to_string())),
+        if self.strings == Strings::Verbatim {
+            return Ok(Value::String(v.to_string()));
+        }
+
+        if let Some(hash) = crate::hash_from_hex_literal(v) {
+            return Ok(Value::Hash(hash));
+        }
+
+        match v.strip_prefix('\\') {
+            Some(escaped) if self.strings == Strings::Escaped => {
+                Ok(Value::String(escaped.to_string()))
             }
-        } else {
-            Ok(Value::String(v.to_string()))
+            _ => Ok(Value::String(v.to_string())),
         }
     }
 
@@ -742,7 +1510,7 @@ impl<'de> Visitor<'de> for ValueVisitor {
     {
         let mut list = Vec::with_capacity(seq.size_hint().unwrap_or_default());
 
-        while let Some(next) = seq.next_element::<Value>()? {
+        while let Some(next) = seq.next_element_seed(self)? {
             list.push(next);
         }
 
@@ -755,7 +1523,8 @@ impl<'de> Visitor<'de> for ValueVisitor {
     {
         let mut object = IndexMap::with_capacity(map.size_hint().unwrap_or_default());
 
-        while let Some((k, v)) = map.next_entry::<Hash40, Value>()? {
+        while let Some(k) = map.next_key::<Hash40>()? {
+            let v = map.next_value_seed(self)?;
             object.insert(k, v);
         }
 
@@ -779,7 +1548,13 @@ impl<'de> Deserialize<'de> for Value {
     where
         D: Deserializer<'de>,
     {
-        deserializer.deserialize_any(ValueVisitor)
+        let strings = if deserializer.is_human_readable() {
+            Strings::Unescaped
+        } else {
+            Strings::Verbatim
+        };
+
+        deserializer.deserialize_any(ValueVisitor::new().strings(strings))
     }
 }
 
@@ -791,6 +1566,10 @@ enum MapKeyDeserializer {
 impl<'de> Deserializer<'de> for MapKeyDeserializer {
     type Error = Error;
 
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
@@ -831,9 +1610,20 @@ impl<'de> Deserializer<'de> for MapKeyDeserializer {
         }
     }
 
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
     forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u128 f32 f64 char
-        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        bytes byte_buf option unit unit_struct seq tuple
         tuple_struct map struct enum identifier ignored_any
     }
 }
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read, Write},
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use hash40::Hash40;
+use indexmap::IndexMap;
+
+use crate::{
+    de,
+    file::{ParamFile, ParamRef},
+    ser::{self, ParamSizes, References},
+    ParamId, Value,
+};
+
+/// Where a param pointed into the reference data in the original file
+enum Layout {
+    Leaf,
+    String(u32),
+    List(Vec<Layout>),
+    Map(u32, IndexMap<Hash40, Layout>),
+}
+
+impl Layout {
+    fn load(param: ParamRef<'_>) -> Result<(Value, Self), de::Error> {
+        match param.param_id() {
+            ParamId::List => {
+                let (values, layouts) = param
+                    .elements()?
+                    .into_iter()
+                    .map(Self::load)
+                    .collect::<Result<(Vec<_>, Vec<_>), _>>()?;
+
+                Ok((Value::List(values), Self::List(layouts)))
+            }
+            ParamId::Map => {
+                let mut values = IndexMap::new();
+                let mut layouts = IndexMap::new();
+                for (key, param) in param.entries()? {
+                    let (value, layout) = Self::load(param)?;
+                    values.insert(key, value);
+                    layouts.insert(key, layout);
+                }
+
+                Ok((
+                    Value::Map(values),
+                    Self::Map(param.reference_offset(ParamId::Map)?, layouts),
+                ))
+            }
+            ParamId::String => Ok((
+                param.to_value()?,
+                Self::String(param.reference_offset(ParamId::String)?),
+            )),
+            _ => Ok((param.to_value()?, Self::Leaf)),
+        }
+    }
+}
+
+/// A param file which remembers the layout it was read with.
+///
+/// Writing an unmodified document reproduces the original file byte for byte: the hash table
+/// keeps its order, the reference data is kept as is, and every string and map param points at
+/// the same reference entry it did originally. Anything which was edited is appended to the end
+/// of the hash table or reference data, so the rest of the file stays untouched.
+///
+/// The body of the file is always written depth-first, which is how the game's own files are laid
+/// out.
+pub struct Document {
+    root: Value,
+    layout: Layout,
+    hashes: Vec<Hash40>,
+    reference: Vec<u8>,
+}
+
+impl Document {
+    /// Reads a document from an in-memory param file
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, de::Error> {
+        let file = ParamFile::from_slice(bytes)?;
+        let (root, layout) = Layout::load(file.root()?)?;
+
+        Ok(Self {
+            root,
+            layout,
+            hashes: file.hashes().to_vec(),
+            reference: file.reference().to_vec(),
+        })
+    }
+
+    /// Reads a document from a param file
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, de::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_slice(&bytes)
+    }
+
+    /// The root param of the document
+    pub fn root(&self) -> &Value {
+        &self.root
+    }
+
+    /// The root param of the document, for editing
+    pub fn root_mut(&mut self) -> &mut Value {
+        &mut self.root
+    }
+
+    /// Discards the original layout and returns the root param
+    pub fn into_value(self) -> Value {
+        self.root
+    }
+
+    /// Writes the document, reusing the original layout wherever the params are unchanged
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), ser::Error> {
+        let sizes = ParamSizes::new(&self.root);
+        let mut references = DocumentReferences::new(self, &sizes);
+        references.visit_hashes(&self.root);
+        references.visit(0, &self.root, Some(&self.layout));
+
+        writer.write_all(b"paracobn")?;
+        writer.write_u32::<LittleEndian>(8 * references.hashes.len() as u32)?;
+        writer.write_u32::<LittleEndian>(references.reference.len() as u32)?;
+
+        for hash in references.hashes.iter() {
+            writer.write_u64::<LittleEndian>(hash.0)?;
+        }
+
+        writer.write_all(&references.reference)?;
+
+        ser::write_value(&mut writer, &mut references, &sizes, &self.root)
+    }
+
+    /// Writes the document to a new vector
+    pub fn to_vec(&self) -> Result<Vec<u8>, ser::Error> {
+        let mut writer = Cursor::new(Vec::with_capacity(256));
+        self.write(&mut writer)?;
+
+        Ok(writer.into_inner())
+    }
+}
+
+impl From<Value> for Document {
+    /// Creates a document with no original layout to preserve
+    fn from(root: Value) -> Self {
+        Self {
+            root,
+            layout: Layout::Leaf,
+            hashes: vec![],
+            reference: vec![],
+        }
+    }
+}
+
+struct DocumentReferences<'a> {
+    original: &'a [u8],
+    sizes: &'a ParamSizes,
+    hashes: Vec<Hash40>,
+    hash_lookup: HashMap<Hash40, u32>,
+    reference: Vec<u8>,
+    strings: HashMap<&'a str, u32>,
+    structs: HashMap<Vec<u8>, u32>,
+
+    /// The reference offset of every string and map param, in the order they are written
+    offsets: Vec<u32>,
+    next: usize,
+}
+
+impl<'a> DocumentReferences<'a> {
+    fn new(document: &'a Document, sizes: &'a ParamSizes) -> Self {
+        let mut hash_lookup = HashMap::new();
+        for (index, hash) in document.hashes.iter().enumerate() {
+            hash_lookup.entry(*hash).or_insert(index as u32);
+        }
+
+        Self {
+            original: &document.reference,
+            sizes,
+            hashes: document.hashes.clone(),
+            hash_lookup,
+            reference: document.reference.clone(),
+            strings: HashMap::new(),
+            structs: HashMap::new(),
+            offsets: vec![],
+            next: 0,
+        }
+    }
+
+    fn add_hash(&mut self, hash: Hash40) {
+        if !self.hash_lookup.contains_key(&hash) {
+            self.hash_lookup.insert(hash, self.hashes.len() as u32);
+            self.hashes.push(hash);
+        }
+    }
+
+    fn visit_hashes(&mut self, value: &Value) {
+        match value {
+            Value::Hash(hash) => self.add_hash(*hash),
+            Value::List(values) => values.iter().for_each(|value| self.visit_hashes(value)),
+            Value::Map(map) => {
+                for (key, value) in map.iter() {
+                    self.add_hash(*key);
+                    self.visit_hashes(value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `bytes` can be found at `offset` in the original reference data
+    fn original_matches(&self, offset: u32, bytes: &[u8]) -> bool {
+        let offset = offset as usize;
+        self.original.get(offset..offset + bytes.len()) == Some(bytes)
+    }
+
+    fn string_offset(&mut self, string: &'a str, layout: Option<&Layout>) -> u32 {
+        if let Some(Layout::String(offset)) = layout {
+            if self.original_matches(*offset, string.as_bytes())
+                && self.original.get(*offset as usize + string.len()) == Some(&b'\0')
+            {
+                self.strings.entry(string).or_insert(*offset);
+                return *offset;
+            }
+        }
+
+        if let Some(offset) = self.strings.get(string) {
+            return *offset;
+        }
+
+        let offset = self.reference.len() as u32;
+        self.reference.extend_from_slice(string.as_bytes());
+        self.reference.push(b'\0');
+        self.strings.insert(string, offset);
+        offset
+    }
+
+    fn struct_offset(
+        &mut self,
+        index: usize,
+        map: &IndexMap<Hash40, Value>,
+        layout: Option<&Layout>,
+    ) -> u32 {
+        let table = self
+            .sizes
+            .struct_table(index, map, |key| self.hash_lookup[key]);
+
+        if let Some(Layout::Map(offset, _)) = layout {
+            if self.original_matches(*offset, &table) {
+                self.structs.entry(table).or_insert(*offset);
+                return *offset;
+            }
+        }
+
+        if let Some(offset) = self.structs.get(&table) {
+            return *offset;
+        }
+
+        let offset = self.reference.len() as u32;
+        self.reference.extend_from_slice(&table);
+        self.structs.insert(table, offset);
+        offset
+    }
+
+    /// Resolves the reference offsets of `value` and its children in the order they are written,
+    /// reusing the original entry from `layout` when it still matches
+    fn visit(&mut self, index: usize, value: &'a Value, layout: Option<&Layout>) {
+        match value {
+            Value::String(string) => {
+                let offset = self.string_offset(string, layout);
+                self.offsets.push(offset);
+            }
+            Value::List(values) => {
+                let layouts = match layout {
+                    Some(Layout::List(layouts)) => layouts.as_slice(),
+                    _ => &[],
+                };
+
+                let children = self.sizes.children(index);
+                for (position, (child, value)) in children.zip(values.iter()).enumerate() {
+                    self.visit(child, value, layouts.get(position));
+                }
+            }
+            Value::Map(map) => {
+                let offset = self.struct_offset(index, map, layout);
+                self.offsets.push(offset);
+
+                let layouts = match layout {
+                    Some(Layout::Map(_, layouts)) => Some(layouts),
+                    _ => None,
+                };
+
+                for (child, (key, value)) in self.sizes.children(index).zip(map.iter()) {
+                    self.visit(child, value, layouts.and_then(|layouts| layouts.get(key)));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl References for DocumentReferences<'_> {
+    fn hash_index(&mut self, hash: Hash40) -> u32 {
+        self.hash_lookup[&hash]
+    }
+
+    fn reference_offset(&mut self, _value: &Value) -> u32 {
+        let offset = self.offsets[self.next];
+        self.next += 1;
+        offset
+    }
+}
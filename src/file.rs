@@ -0,0 +1,317 @@
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    io::{Cursor, Read},
+};
+
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt};
+use hash40::Hash40;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use crate::{
+    de::{Error, ErrorKind, Header, ReferenceData, ValueDeserializer},
+    ParamId, Value,
+};
+
+/// A param file which is only decoded as it is accessed.
+///
+/// The header, hash table and reference data are parsed once up front. Maps and lists are only
+/// decoded when they are visited through a [`ParamRef`], so pulling a single value out of a large
+/// file does not require deserializing the whole thing.
+pub struct ParamFile<'a> {
+    bytes: Cow<'a, [u8]>,
+    hashes: Vec<Hash40>,
+    reference_offset: usize,
+    reference_len: usize,
+}
+
+impl<'a> ParamFile<'a> {
+    fn new(bytes: Cow<'a, [u8]>) -> Result<Self, Error> {
+        let header = Header::parse(&bytes)?;
+        let reference_offset = header.reference_offset();
+        let reference_len = header.reference.len();
+        let hashes = header.hashes;
+
+        Ok(Self {
+            bytes,
+            hashes,
+            reference_offset,
+            reference_len,
+        })
+    }
+
+    /// Parses the header of an in-memory param file, borrowing the file data
+    pub fn from_slice(bytes: &'a [u8]) -> Result<Self, Error> {
+        Self::new(Cow::Borrowed(bytes))
+    }
+
+    /// Parses the header of an in-memory param file, taking ownership of the file data
+    pub fn from_vec(bytes: Vec<u8>) -> Result<ParamFile<'static>, Error> {
+        ParamFile::new(Cow::Owned(bytes))
+    }
+
+    /// Reads an entire param file into memory and parses the header
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<ParamFile<'static>, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        ParamFile::from_vec(bytes)
+    }
+
+    /// The hash table of the file
+    pub fn hashes(&self) -> &[Hash40] {
+        &self.hashes
+    }
+
+    /// The root param of the file
+    pub fn root(&self) -> Result<ParamRef<'_>, Error> {
+        ParamRef::at(self, self.reference_offset + self.reference_len)
+    }
+
+    /// Shorthand for looking up `key` in the root map
+    pub fn get(&self, key: Hash40) -> Result<ParamRef<'_>, Error> {
+        self.root()?.get(key)
+    }
+
+    /// The raw reference data of the file
+    pub(crate) fn reference(&self) -> &[u8] {
+        &self.bytes[self.reference_offset..self.reference_offset + self.reference_len]
+    }
+
+    fn reference_data(&self) -> ReferenceData<'_> {
+        ReferenceData::new(self.reference(), self.reference_offset)
+    }
+
+    fn cursor_at(&self, offset: usize) -> Cursor<&[u8]> {
+        let mut cursor = Cursor::new(self.bytes.as_ref());
+        cursor.set_position(offset as u64);
+        cursor
+    }
+}
+
+/// A handle to a single param inside of a [`ParamFile`]
+#[derive(Copy, Clone)]
+pub struct ParamRef<'f> {
+    file: &'f ParamFile<'f>,
+    offset: usize,
+    id: ParamId,
+}
+
+impl Debug for ParamRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParamRef")
+            .field("id", &self.id)
+            .field("offset", &self.offset)
+            .finish()
+    }
+}
+
+impl<'f> ParamRef<'f> {
+    fn at(file: &'f ParamFile<'f>, offset: usize) -> Result<Self, Error> {
+        let id = file.cursor_at(offset).read_u8()?;
+        let id = ParamId::try_from(id).map_err(ErrorKind::InvalidParamId)?;
+
+        Ok(Self { file, offset, id })
+    }
+
+    fn expect(&self, expected: ParamId) -> Result<(), Error> {
+        if self.id == expected {
+            Ok(())
+        } else {
+            Err(Error::from(ErrorKind::UnexpectedParam {
+                expected,
+                found: self.id,
+            }))
+        }
+    }
+
+    /// The type of this param
+    pub fn param_id(&self) -> ParamId {
+        self.id
+    }
+
+    /// The absolute position of this param within the file
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The offset into the reference data that this param points at, which has to be an
+    /// `expected` string or map param
+    pub(crate) fn reference_offset(&self, expected: ParamId) -> Result<u32, Error> {
+        let position = match (expected, self.id) {
+            (ParamId::String, ParamId::String) => self.offset + 1,
+            (ParamId::Map, ParamId::Map) => self.offset + 5,
+            (expected, found) => {
+                return Err(Error::from(ErrorKind::UnexpectedParam { expected, found }))
+            }
+        };
+
+        Ok(self.file.cursor_at(position).read_u32::<LittleEndian>()?)
+    }
+
+    /// The number of elements in this list or entries in this map
+    pub fn len(&self) -> Result<usize, Error> {
+        match self.id {
+            ParamId::List | ParamId::Map => Ok(self
+                .file
+                .cursor_at(self.offset + 1)
+                .read_u32::<LittleEndian>()?
+                as usize),
+            found => Err(Error::from(ErrorKind::UnexpectedParam {
+                expected: ParamId::List,
+                found,
+            })),
+        }
+    }
+
+    /// Whether this list or map has no elements
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// The elements of this list
+    pub fn elements(&self) -> Result<Vec<ParamRef<'f>>, Error> {
+        self.expect(ParamId::List)?;
+
+        // Same layout as the list branch of `ValueDeserializer::deserialize_any`: a count followed
+        // by offsets relative to the list param
+        let mut cursor = self.file.cursor_at(self.offset + 1);
+        let num_elements = cursor.read_u32::<LittleEndian>()?;
+
+        let mut offsets = Vec::new();
+        for _ in 0..num_elements {
+            offsets.push(self.offset + cursor.read_u32::<LittleEndian>()? as usize);
+        }
+
+        offsets
+            .into_iter()
+            .map(|offset| ParamRef::at(self.file, offset))
+            .collect()
+    }
+
+    /// The element of this list at `index`
+    pub fn index(&self, index: usize) -> Result<ParamRef<'f>, Error> {
+        self.expect(ParamId::List)?;
+
+        let mut cursor = self.file.cursor_at(self.offset + 1);
+        let len = cursor.read_u32::<LittleEndian>()? as usize;
+        if index >= len {
+            return Err(Error::from(ErrorKind::IndexOutOfBounds { index, len }));
+        }
+
+        cursor.set_position((self.offset + 5 + index * 4) as u64);
+        let offset = self.offset + cursor.read_u32::<LittleEndian>()? as usize;
+        ParamRef::at(self.file, offset)
+    }
+
+    /// The keys and values of this map, in file order
+    pub fn entries(&self) -> Result<Vec<(Hash40, ParamRef<'f>)>, Error> {
+        self.expect(ParamId::Map)?;
+
+        let mut cursor = self.file.cursor_at(self.offset + 1);
+        let num_elements = cursor.read_u32::<LittleEndian>()? as usize;
+        let ref_position = cursor.read_u32::<LittleEndian>()?;
+
+        self.file
+            .reference_data()
+            .get_map(
+                &self.file.hashes,
+                ref_position,
+                num_elements,
+                self.offset as u64,
+            )?
+            .into_iter()
+            .map(|(key, offset)| Ok((key, ParamRef::at(self.file, offset as usize)?)))
+            .collect()
+    }
+
+    /// The value of this map at `key`. Only the matching entry is decoded.
+    pub fn get(&self, key: Hash40) -> Result<ParamRef<'f>, Error> {
+        self.expect(ParamId::Map)?;
+
+        let mut cursor = self.file.cursor_at(self.offset + 1);
+        let num_elements = cursor.read_u32::<LittleEndian>()? as usize;
+        let ref_position = cursor.read_u32::<LittleEndian>()? as usize;
+
+        // Same layout as `ReferenceData::get_map`: pairs of a hash index and an offset relative to
+        // the map param
+        let table = self
+            .file
+            .reference()
+            .get(ref_position..ref_position + num_elements * 8)
+            .ok_or_else(|| {
+                Error::from(ErrorKind::MapRefOutOfBounds {
+                    start: self.file.reference_offset + ref_position,
+                    num_elements,
+                })
+            })?;
+
+        for entry in table.chunks_exact(8) {
+            let hash_index = LittleEndian::read_u32(&entry[..4]) as usize;
+            let Some(hash) = self.file.hashes.get(hash_index) else {
+                return Err(Error::from(ErrorKind::HashOutOfBounds(hash_index)));
+            };
+
+            if *hash == key {
+                let offset = LittleEndian::read_u32(&entry[4..]) as usize;
+                return ParamRef::at(self.file, self.offset + offset);
+            }
+        }
+
+        Err(Error::from(ErrorKind::MissingKey(key)))
+    }
+
+    /// Fully decodes this param and everything beneath it
+    pub fn to_value(&self) -> Result<Value, Error> {
+        self.deserialize()
+    }
+
+    /// Deserializes this param and everything beneath it
+    pub fn deserialize<T: Deserialize<'f>>(&self) -> Result<T, Error> {
+        let mut deserializer = ValueDeserializer::from_slice(
+            self.file.reference_data(),
+            &self.file.hashes,
+            &self.file.bytes,
+            self.offset,
+        );
+
+        T::deserialize(&mut deserializer)
+    }
+}
+
+/// Parallel decoding, enabled by the `rayon` feature.
+///
+/// Every param can be decoded on its own given the file, so each worker gets its own
+/// [`ParamRef`] to a list element and reads it through its own slice cursor, sharing the hash
+/// table and reference data of the file.
+#[cfg(feature = "rayon")]
+impl<'f> ParamRef<'f> {
+    /// Fully decodes this param and everything beneath it, decoding the elements of every list
+    /// in parallel
+    pub fn par_to_value(&self) -> Result<Value, Error> {
+        match self.id {
+            ParamId::List => self
+                .elements()?
+                .into_par_iter()
+                .map(|element| element.par_to_value())
+                .collect::<Result<_, _>>()
+                .map(Value::List),
+            ParamId::Map => self
+                .entries()?
+                .into_iter()
+                .map(|(key, value)| Ok((key, value.par_to_value()?)))
+                .collect::<Result<_, _>>()
+                .map(Value::Map),
+            _ => self.to_value(),
+        }
+    }
+
+    /// Deserializes every element of this list in parallel
+    pub fn par_elements<T: Deserialize<'f> + Send>(&self) -> Result<Vec<T>, Error> {
+        self.elements()?
+            .into_par_iter()
+            .map(|element| element.deserialize())
+            .collect()
+    }
+}
@@ -0,0 +1,97 @@
+//! Helpers for `String` fields which are stored as hash params.
+//!
+//! ```
+//! # use serde::{Deserialize, Serialize};
+//! #[derive(Serialize, Deserialize)]
+//! struct Chara {
+//!     #[serde(with = "serde_prc::hash_str")]
+//!     chara_id: String,
+//!
+//!     #[serde(default, with = "serde_prc::hash_str::option")]
+//!     original_ui_chara_hash: Option<String>,
+//!
+//!     #[serde(with = "serde_prc::hash_str::vec")]
+//!     color_ids: Vec<String>,
+//! }
+//! ```
+//!
+//! When serializing, the string is turned back into a hash by parsing it as a `0x0123456789` hex
+//! literal or by looking it up in the global label map, falling back on hashing it. When
+//! deserializing, the hash is shown as its label if there is one and as a hex literal otherwise.
+//!
+//! Human readable formats keep the field as a plain string.
+
+use serde::{ser::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::ser::HashParam;
+
+pub fn serialize<S: Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        return serializer.serialize_str(value);
+    }
+
+    match crate::hash_from_label(value) {
+        Some(hash) => HashParam(hash).serialize(serializer),
+        None => Err(S::Error::custom(format!(
+            "No hash found for label '{value}'"
+        ))),
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    String::deserialize(deserializer)
+}
+
+/// Serializes a string through [`serialize`]
+struct HashStr<'a>(&'a str);
+
+impl Serialize for HashStr<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(self.0, serializer)
+    }
+}
+
+/// The same as [`hash_str`](crate::hash_str) for `Option<String>` fields.
+///
+/// Pair this with `#[serde(default)]` so that missing fields deserialize as `None`.
+pub mod option {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::HashStr;
+
+    pub fn serialize<S: Serializer, T: AsRef<str>>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_some(&HashStr(value.as_ref())),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<String>, D::Error> {
+        Option::<String>::deserialize(deserializer)
+    }
+}
+
+/// The same as [`hash_str`](crate::hash_str) for `Vec<String>` fields
+pub mod vec {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::HashStr;
+
+    pub fn serialize<S: Serializer, T: AsRef<str>>(
+        value: &[T],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(value.iter().map(|value| HashStr(value.as_ref())))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        Vec::<String>::deserialize(deserializer)
+    }
+}
@@ -0,0 +1,237 @@
+//! Loading label files into the global label map.
+//!
+//! Two formats are supported: the `hash,label` CSV of ParamLabels, where each line pairs a
+//! `0x0123456789` hex literal with its label, and plain lists with one label per line, where
+//! each hash is computed from its label. Blank lines are skipped in both.
+//!
+//! ```
+//! # use serde_prc::labels::{Conflict, Labels};
+//! # use serde_prc::Hash40;
+//! let mut labels = Labels::parse_csv("0x0123456789,custom_label\n")?;
+//! labels.merge(Labels::parse_list("walk_speed\nrun_speed\n"));
+//! assert_eq!(labels.label_of(Hash40::new("run_speed")), Some("run_speed"));
+//!
+//! // The CSV was loaded first, so it keeps the label
+//! labels.merge(Labels::parse_list("custom_label\n"));
+//! assert_eq!(labels.hash_of("custom_label"), Some(Hash40(0x0123456789)));
+//! assert!(matches!(labels.conflicts(), [Conflict::DuplicateLabel { .. }]));
+//!
+//! labels.install();
+//! assert_eq!(Hash40(0x0123456789).to_label(), "custom_label");
+//! # Ok::<(), serde_prc::labels::Error>(())
+//! ```
+//!
+//! A hash can only have one label and a label can only belong to one hash, otherwise hashes
+//! wouldn't turn back into the same labels. When a source breaks that, the entry which came
+//! first is kept and the other one is recorded as a [`Conflict`]. The same goes for merging:
+//! labels which were loaded first take precedence, so sources should be loaded from the most to
+//! the least trusted.
+
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+use hash40::Hash40;
+use indexmap::IndexMap;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Line {line} is not a `hash,label` pair")]
+    MissingLabel { line: usize },
+
+    #[error("Line {line} has an invalid hash {text:?}")]
+    InvalidHash { line: usize, text: String },
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+}
+
+/// An entry which was left out because it disagrees with one that was loaded before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// The hash already has a different label
+    Collision {
+        hash: Hash40,
+        kept: String,
+        dropped: String,
+    },
+
+    /// The label already belongs to a different hash
+    DuplicateLabel {
+        label: String,
+        kept: Hash40,
+        dropped: Hash40,
+    },
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Collision {
+                hash,
+                kept,
+                dropped,
+            } => write!(
+                f,
+                "{:#012x} is labeled both {kept:?} and {dropped:?}, keeping {kept:?}",
+                hash.0
+            ),
+            Self::DuplicateLabel {
+                label,
+                kept,
+                dropped,
+            } => write!(
+                f,
+                "{label:?} is the label of both {:#012x} and {:#012x}, keeping {:#012x}",
+                kept.0, dropped.0, kept.0
+            ),
+        }
+    }
+}
+
+/// A set of labels where every hash has one label and every label has one hash
+#[derive(Debug, Clone, Default)]
+pub struct Labels {
+    labels: IndexMap<Hash40, String>,
+    hashes: HashMap<String, Hash40>,
+    conflicts: Vec<Conflict>,
+}
+
+impl Labels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses the `hash,label` CSV format
+    pub fn parse_csv(text: &str) -> Result<Self, Error> {
+        let mut labels = Self::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let line_number = index + 1;
+            let (hash, label) = line
+                .split_once(',')
+                .ok_or(Error::MissingLabel { line: line_number })?;
+
+            let (hash, label) = (hash.trim(), label.trim());
+            if label.is_empty() {
+                return Err(Error::MissingLabel { line: line_number });
+            }
+
+            let hash = Hash40::from_hex_str(hash)
+                .ok()
+                .filter(|hash| hash.0 >> 40 == 0)
+                .ok_or_else(|| Error::InvalidHash {
+                    line: line_number,
+                    text: hash.to_string(),
+                })?;
+
+            labels.insert(hash, label);
+        }
+
+        Ok(labels)
+    }
+
+    /// Parses a list of labels, one per line
+    pub fn parse_list(text: &str) -> Self {
+        let mut labels = Self::new();
+        for label in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            labels.insert(Hash40::new(label), label);
+        }
+
+        labels
+    }
+
+    /// Reads a label file, which is parsed as CSV if its first line has a comma and as a list
+    /// otherwise
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path)?;
+        let first_line = text.lines().find(|line| !line.trim().is_empty());
+
+        if first_line.is_some_and(|line| line.contains(',')) {
+            Self::parse_csv(&text)
+        } else {
+            Ok(Self::parse_list(&text))
+        }
+    }
+
+    /// Adds a label, recording a [`Conflict`] instead if either the hash or the label is
+    /// already taken
+    pub fn insert(&mut self, hash: Hash40, label: impl Into<String>) {
+        let label = label.into();
+
+        if let Some(kept) = self.labels.get(&hash) {
+            if *kept != label {
+                self.conflicts.push(Conflict::Collision {
+                    hash,
+                    kept: kept.clone(),
+                    dropped: label,
+                });
+            }
+            return;
+        }
+
+        if let Some(kept) = self.hashes.get(&label) {
+            self.conflicts.push(Conflict::DuplicateLabel {
+                label,
+                kept: *kept,
+                dropped: hash,
+            });
+            return;
+        }
+
+        self.hashes.insert(label.clone(), hash);
+        self.labels.insert(hash, label);
+    }
+
+    /// Adds the labels of `other` which don't conflict with these ones, along with its
+    /// conflicts
+    pub fn merge(&mut self, other: Labels) {
+        self.conflicts.extend(other.conflicts);
+        for (hash, label) in other.labels {
+            self.insert(hash, label);
+        }
+    }
+
+    pub fn label_of(&self, hash: Hash40) -> Option<&str> {
+        self.labels.get(&hash).map(String::as_str)
+    }
+
+    pub fn hash_of(&self, label: &str) -> Option<Hash40> {
+        self.hashes.get(label).copied()
+    }
+
+    /// The labels in the order they were loaded
+    pub fn iter(&self) -> impl Iterator<Item = (Hash40, &str)> {
+        self.labels
+            .iter()
+            .map(|(hash, label)| (*hash, label.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// Every entry which was left out while loading and merging, in the order they were found
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Adds these labels to the global label map used for displaying and parsing hashes,
+    /// replacing any labels it already has for the same hashes or labels
+    pub fn install(&self) {
+        let labels = Hash40::label_map();
+        let mut labels = labels.lock().unwrap_or_else(|error| error.into_inner());
+        labels.add_custom_labels(
+            self.labels
+                .iter()
+                .map(|(hash, label)| (*hash, label.clone())),
+        );
+    }
+}
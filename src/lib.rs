@@ -1,14 +1,23 @@
 use std::fmt::Debug;
 
-use byteorder::{LittleEndian, ReadBytesExt};
 pub use hash40::Hash40;
 use indexmap::IndexMap;
 use serde::Deserialize;
 
-use crate::de::{ReferenceData, ValueDeserializer};
+use crate::de::{Header, ReferenceData, ValueDeserializer};
 pub mod de;
+pub mod document;
+pub mod file;
+pub mod hash_str;
+pub mod labels;
 pub mod ser;
+pub mod tagged;
+pub mod text;
+#[cfg(feature = "xml")]
+pub mod xml;
 
+pub use document::Document;
+pub use file::ParamFile;
 pub use ser::to_vec;
 
 #[cfg(test)]
@@ -29,6 +38,17 @@ macro_rules! decl_id {
             )*
         }
 
+        impl Value {
+            /// The type of param this value is written as
+            pub fn param_id(&self) -> ParamId {
+                match self {
+                    $(
+                        Self::$name(_) => ParamId::$name,
+                    )*
+                }
+            }
+        }
+
         $(
             impl From<$t> for Value {
                 fn from(value: $t) -> Self {
@@ -287,38 +307,104 @@ impl Value {
     }
 }
 
-pub fn from_reader<T: for<'de> Deserialize<'de>, R: std::io::Read + std::io::Seek>(
-    mut reader: R,
-) -> Result<T, de::Error> {
-    // Check magic
-    let mut magic = [0u8; 8];
-    reader.read_exact(&mut magic).unwrap();
+/// Parses a hash written the way unlabeled hashes are displayed, `0x0123456789`
+pub(crate) fn hash_from_hex_literal(string: &str) -> Option<Hash40> {
+    let digits = string.strip_prefix("0x")?;
+    if digits.len() != 10 || !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
 
-    assert_eq!(magic, *b"paracobn");
+    u64::from_str_radix(digits, 16).ok().map(Hash40)
+}
 
-    let hash_data_size = reader.read_u32::<LittleEndian>().unwrap();
-    assert_eq!(hash_data_size % 8, 0);
-    let ref_data_size = reader.read_u32::<LittleEndian>().unwrap();
+/// The reverse of displaying a hash: hex literals are parsed and anything else is looked up in
+/// the global label map. Returns `None` if the label map is strict and has no such label.
+///
+/// Unlike [`Hash40::from_label`], only `0x0123456789` literals with exactly ten digits count as
+/// hex, so a label such as `0x1` is hashed like any other label.
+///
+/// ```
+/// # use serde_prc::{hash_from_label, Hash40};
+/// assert_eq!(hash_from_label("0x0123456789"), Some(Hash40(0x0123456789)));
+/// assert_eq!(hash_from_label("0x1"), Some(Hash40::new("0x1")));
+/// ```
+pub fn hash_from_label(label: &str) -> Option<Hash40> {
+    if let Some(hash) = hash_from_hex_literal(label) {
+        return Some(hash);
+    }
 
-    let hashes: Vec<_> = (0..hash_data_size / 8)
-        .map(|_| Hash40(reader.read_u64::<LittleEndian>().unwrap()))
-        .collect();
+    let labels = Hash40::label_map();
+    let labels = labels.lock().unwrap_or_else(|error| error.into_inner());
+    labels.hash_of(label)
+}
 
-    let mut ref_data = Vec::with_capacity(ref_data_size as usize);
-    unsafe {
-        ref_data.set_len(ref_data_size as usize);
-        reader.read_exact(&mut ref_data).unwrap();
-    }
+pub fn from_reader<T: for<'de> Deserialize<'de>, R: std::io::Read + std::io::Seek>(
+    reader: R,
+) -> Result<T, de::Error> {
+    from_reader_with(reader, de::Options::default())
+}
+
+/// Deserializes a value from a param file, using `options` to decide how strictly params have
+/// to match the types they are deserialized into
+pub fn from_reader_with<T: for<'de> Deserialize<'de>, R: std::io::Read + std::io::Seek>(
+    mut reader: R,
+    options: de::Options,
+) -> Result<T, de::Error> {
+    let header = Header::read(&mut reader)?;
+    let reference_offset = header.reference_offset();
 
     let mut deserializer = ValueDeserializer::new(
-        ReferenceData::new(ref_data, 8 + hash_data_size as usize),
-        &hashes,
+        ReferenceData::new(header.reference, reference_offset),
+        &header.hashes,
         &mut reader,
-    );
+    )
+    .with_options(options);
+
+    T::deserialize(&mut deserializer)
+}
+
+/// Deserializes a value from an in-memory param file.
+///
+/// Unlike [`from_reader`], the reference data is borrowed straight from `bytes` instead of being
+/// copied out, so strings can be deserialized as `&'de str` without allocating.
+pub fn from_slice<'de, T: Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, de::Error> {
+    from_slice_with(bytes, de::Options::default())
+}
+
+/// Deserializes a value from an in-memory param file, using `options` to decide how strictly
+/// params have to match the types they are deserialized into
+pub fn from_slice_with<'de, T: Deserialize<'de>>(
+    bytes: &'de [u8],
+    options: de::Options,
+) -> Result<T, de::Error> {
+    let header = Header::parse(bytes)?;
+    let reference_offset = header.reference_offset();
+    let body_offset = header.body_offset();
+
+    let mut deserializer = ValueDeserializer::from_slice(
+        ReferenceData::new(header.reference, reference_offset),
+        &header.hashes,
+        bytes,
+        body_offset,
+    )
+    .with_options(options);
 
     T::deserialize(&mut deserializer)
 }
 
-pub fn from_slice<T: for<'de> Deserialize<'de>>(bytes: &[u8]) -> Result<T, de::Error> {
-    from_reader(std::io::Cursor::new(bytes))
+/// Decodes an in-memory param file into a [`Value`], decoding the elements of every list in
+/// parallel
+#[cfg(feature = "rayon")]
+pub fn par_value_from_slice(bytes: &[u8]) -> Result<Value, de::Error> {
+    ParamFile::from_slice(bytes)?.root()?.par_to_value()
+}
+
+/// Deserializes an in-memory param file whose root is a list, deserializing the elements in
+/// parallel. For a list further down the file, use [`file::ParamRef::par_elements`].
+#[cfg(feature = "rayon")]
+pub fn par_list_from_slice<T>(bytes: &[u8]) -> Result<Vec<T>, de::Error>
+where
+    T: for<'de> Deserialize<'de> + Send,
+{
+    ParamFile::from_slice(bytes)?.root()?.par_elements()
 }
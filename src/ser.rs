@@ -1,7 +1,7 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     hash::{DefaultHasher, Hasher},
-    io::{Cursor, Write},
+    io::{Cursor, Seek, Write},
 };
 
 use byteorder::{LittleEndian, WriteBytesExt};
@@ -9,8 +9,8 @@ use hash40::Hash40;
 use indexmap::{IndexMap, IndexSet};
 use serde::{
     ser::{
-        Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeTuple,
-        SerializeTupleStruct,
+        Impossible, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+        SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
     },
     Serialize, Serializer,
 };
@@ -19,6 +19,10 @@ use crate::{ParamId, Value};
 
 use thiserror::Error;
 
+mod stream;
+
+pub use stream::{write_streaming, write_streaming_with};
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Unsupported key type: '{0}'")]
@@ -27,6 +31,23 @@ pub enum Error {
     #[error("Unsupported value type: '{0}'")]
     UnsupportedValueType(&'static str),
 
+    #[error("None can only be used for struct fields and map values")]
+    UnsupportedNone,
+
+    #[error("No hash found for label '{0}'")]
+    UnknownLabel(String),
+
+    #[error("{0} can't be represented as an f32 without losing precision")]
+    LossyFloat(f64),
+
+    #[error("A list said it had {hint} elements but had more")]
+    ListTooLong { hint: usize },
+
+    #[error(
+        "The hash table and reference data need {needed:#x} bytes but only {reserved:#x} were reserved"
+    )]
+    ReserveTooSmall { needed: usize, reserved: usize },
+
     #[error(transparent)]
     IO(#[from] std::io::Error),
 
@@ -43,29 +64,216 @@ impl serde::ser::Error for Error {
     }
 }
 
-pub struct IntoValueSerializer;
+/// How `u64` values are written
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum U64Mapping {
+    /// Write them as hashes
+    #[default]
+    Hash,
+
+    /// Write them as `u32` params, failing if they don't fit
+    U32,
+}
+
+/// How `i64` values are written
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum I64Mapping {
+    /// Write them as `i32` params, failing if they don't fit
+    #[default]
+    I32,
+
+    /// Refuse to write them
+    Reject,
+}
+
+/// The order of the hashes in the hash table
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum HashOrder {
+    /// The order they are first seen in while walking the value
+    #[default]
+    FirstSeen,
+
+    /// Sorted by their value
+    Sorted,
+}
+
+/// Controls how values are converted to params and how the file is laid out.
+///
+/// The defaults match what [`write`] and [`to_vec`] do.
+///
+/// ```
+/// # use serde_prc::ser::{Options, U64Mapping};
+/// let bytes = Options::new()
+///     .u64_mapping(U64Mapping::U32)
+///     .lossy_floats(false)
+///     .to_vec(&vec![1u64, 2, 3])
+///     .unwrap();
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Options {
+    u64_mapping: U64Mapping,
+    i64_mapping: I64Mapping,
+    lossy_floats: bool,
+    dedup_strings: bool,
+    share_structs: bool,
+    hash_order: HashOrder,
+    streaming_reserve: u32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Options {
+    pub const fn new() -> Self {
+        Self {
+            u64_mapping: U64Mapping::Hash,
+            i64_mapping: I64Mapping::I32,
+            lossy_floats: true,
+            dedup_strings: true,
+            share_structs: true,
+            hash_order: HashOrder::FirstSeen,
+            streaming_reserve: 0x10000,
+        }
+    }
+
+    pub const fn u64_mapping(mut self, mapping: U64Mapping) -> Self {
+        self.u64_mapping = mapping;
+        self
+    }
+
+    pub const fn i64_mapping(mut self, mapping: I64Mapping) -> Self {
+        self.i64_mapping = mapping;
+        self
+    }
+
+    /// Whether an `f64` which doesn't fit in an `f32` is rounded (the default) or refused
+    pub const fn lossy_floats(mut self, lossy: bool) -> Self {
+        self.lossy_floats = lossy;
+        self
+    }
+
+    /// Whether identical strings share a single copy in the reference data
+    pub const fn dedup_strings(mut self, dedup: bool) -> Self {
+        self.dedup_strings = dedup;
+        self
+    }
+
+    /// Whether maps with the same layout share a single struct table in the reference data
+    pub const fn share_structs(mut self, share: bool) -> Self {
+        self.share_structs = share;
+        self
+    }
+
+    pub const fn hash_order(mut self, order: HashOrder) -> Self {
+        self.hash_order = order;
+        self
+    }
+
+    /// How many bytes [`write_streaming`] sets aside in front of the body for the hash table and
+    /// reference data, 64 KiB by default. Space they don't need is padded with zeros, and
+    /// writing fails with [`Error::ReserveTooSmall`] if they need more.
+    pub const fn streaming_reserve(mut self, bytes: u32) -> Self {
+        self.streaming_reserve = bytes;
+        self
+    }
+
+    /// Writes `value` using these options, see [`write_with`]
+    pub fn write<W: Write, T: Serialize>(self, writer: W, value: &T) -> Result<(), Error> {
+        write_with(writer, value, self)
+    }
+
+    /// Writes `value` without building a [`Value`] first using these options, see
+    /// [`write_streaming_with`]
+    pub fn write_streaming<W: Write + Seek, T: Serialize>(
+        self,
+        writer: W,
+        value: &T,
+    ) -> Result<(), Error> {
+        write_streaming_with(writer, value, self)
+    }
+
+    /// Serializes `value` using these options, see [`to_vec_with`]
+    pub fn to_vec<T: Serialize>(self, value: &T) -> Result<Vec<u8>, Error> {
+        to_vec_with(value, self)
+    }
+}
+
+/// The name of the newtype struct which [`HashParam`] serializes as. The serializers in this
+/// crate turn its `u64` straight into a hash param, other serializers just see the `u64`.
+const HASH_NEWTYPE: &str = "$serde_prc::HashParam";
+
+/// A hash which is always written as a hash param, no matter how `u64` values are mapped by
+/// [`Options::u64_mapping`]
+pub(crate) struct HashParam(pub Hash40);
+
+impl Serialize for HashParam {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_newtype_struct(HASH_NEWTYPE, &self.0 .0)
+    }
+}
+
+#[derive(Default)]
+pub struct IntoValueSerializer {
+    options: Options,
+}
+
+impl IntoValueSerializer {
+    pub fn new(options: Options) -> Self {
+        Self { options }
+    }
+}
+
+/// Serializes a value which can't be left out, such as a list element
+fn required<T: ?Sized + Serialize>(value: &T, options: Options) -> Result<Value, Error> {
+    value
+        .serialize(IntoValueSerializer::new(options))
+        .map_err(|error| match error {
+            // Don't let the enclosing struct mistake this for one of its own fields being `None`
+            Error::UnsupportedNone => Error::UnsupportedValueType("none"),
+            error => error,
+        })
+}
+
+/// Serializes a value which is left out when it is `None`, such as a struct field
+fn optional<T: ?Sized + Serialize>(value: &T, options: Options) -> Result<Option<Value>, Error> {
+    match value.serialize(IntoValueSerializer::new(options)) {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::UnsupportedNone) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
 
-pub struct ListSerializer(Vec<Value>);
+pub struct ListSerializer {
+    list: Vec<Value>,
+    options: Options,
+}
 
 pub struct MapSerializer {
     map: IndexMap<Hash40, Value>,
     current_key: Option<Hash40>,
+    options: Options,
 }
 
 impl SerializeSeq for ListSerializer {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.0.push(value.serialize(IntoValueSerializer)?);
+        self.list.push(required(value, self.options)?);
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::List(self.0))
+        Ok(Value::List(self.list))
     }
 }
 
@@ -73,16 +281,16 @@ impl SerializeTuple for ListSerializer {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.0.push(value.serialize(IntoValueSerializer)?);
+        self.list.push(required(value, self.options)?);
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::List(self.0))
+        Ok(Value::List(self.list))
     }
 }
 
@@ -90,16 +298,16 @@ impl SerializeTupleStruct for ListSerializer {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        self.0.push(value.serialize(IntoValueSerializer)?);
+        self.list.push(required(value, self.options)?);
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::List(self.0))
+        Ok(Value::List(self.list))
     }
 }
 
@@ -107,29 +315,29 @@ impl SerializeMap for MapSerializer {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), Self::Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let key = key.serialize(HashSerializer)?;
         self.current_key = Some(key);
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Self::Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        if !self.current_key.is_some() {
+        if self.current_key.is_none() {
             return Err(Error::Custom(
                 "attempting to serialize value with no key".to_string(),
             ));
         }
 
-        let value = value.serialize(IntoValueSerializer)?;
         let key = self.current_key.take().unwrap();
-
-        self.map.insert(key, value);
+        if let Some(value) = optional(value, self.options)? {
+            self.map.insert(key, value);
+        }
 
         Ok(())
     }
@@ -143,18 +351,13 @@ impl SerializeStruct for MapSerializer {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(
-        &mut self,
-        key: &'static str,
-        value: &T,
-    ) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        let key = hash40::hash40(key);
-        let value = value.serialize(IntoValueSerializer)?;
-
-        self.map.insert(key, value);
+        if let Some(value) = optional(value, self.options)? {
+            self.map.insert(hash40::hash40(key), value);
+        }
 
         Ok(())
     }
@@ -164,6 +367,51 @@ impl SerializeStruct for MapSerializer {
     }
 }
 
+/// Serializes the data of an enum variant, wrapping it in a single-entry map keyed by the
+/// variant name when finished
+pub struct VariantSerializer<S> {
+    variant: Hash40,
+    inner: S,
+}
+
+fn variant_map(variant: Hash40, value: Value) -> Value {
+    let mut map = IndexMap::with_capacity(1);
+    map.insert(variant, value);
+    Value::Map(map)
+}
+
+impl SerializeTupleVariant for VariantSerializer<ListSerializer> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(&mut self.inner, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(variant_map(self.variant, SerializeSeq::end(self.inner)?))
+    }
+}
+
+impl SerializeStructVariant for VariantSerializer<MapSerializer> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(variant_map(self.variant, SerializeStruct::end(self.inner)?))
+    }
+}
+
 macro_rules! e {
     ($e:literal) => {
         Err(Error::UnsupportedValueType($e))
@@ -180,13 +428,13 @@ impl Serializer for IntoValueSerializer {
 
     type SerializeTupleStruct = ListSerializer;
 
-    type SerializeTupleVariant = Impossible<Value, Error>;
+    type SerializeTupleVariant = VariantSerializer<ListSerializer>;
 
     type SerializeMap = MapSerializer;
 
     type SerializeStruct = MapSerializer;
 
-    type SerializeStructVariant = Impossible<Value, Error>;
+    type SerializeStructVariant = VariantSerializer<MapSerializer>;
 
     fn is_human_readable(&self) -> bool {
         false
@@ -217,13 +465,21 @@ impl Serializer for IntoValueSerializer {
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::I32(
-            v.try_into().map_err(<Error as serde::ser::Error>::custom)?,
-        ))
+        match self.options.i64_mapping {
+            I64Mapping::I32 => Ok(Value::I32(
+                v.try_into().map_err(<Error as serde::ser::Error>::custom)?,
+            )),
+            I64Mapping::Reject => e!("i64"),
+        }
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::Hash(Hash40(v)))
+        match self.options.u64_mapping {
+            U64Mapping::Hash => Ok(Value::Hash(Hash40(v))),
+            U64Mapping::U32 => Ok(Value::U32(
+                v.try_into().map_err(<Error as serde::ser::Error>::custom)?,
+            )),
+        }
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
@@ -231,7 +487,12 @@ impl Serializer for IntoValueSerializer {
     }
 
     fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::F32(v as f32))
+        let narrowed = v as f32;
+        if !self.options.lossy_floats && !v.is_nan() && narrowed as f64 != v {
+            return Err(Error::LossyFloat(v));
+        }
+
+        Ok(Value::F32(narrowed))
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
@@ -251,14 +512,14 @@ impl Serializer for IntoValueSerializer {
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-        e!("none")
+        Err(Error::UnsupportedNone)
     }
 
-    fn serialize_some<T: ?Sized>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        e!("some")
+        value.serialize(self)
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
@@ -278,36 +539,49 @@ impl Serializer for IntoValueSerializer {
         Ok(Value::String(variant.to_string()))
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
-        _name: &'static str,
-        _value: &T,
+        name: &'static str,
+        value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        e!("newtype struct")
+        if name == HASH_NEWTYPE {
+            return value.serialize(HashSerializer).map(Value::Hash);
+        }
+
+        value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        e!("newtype variant")
+        Ok(variant_map(
+            hash40::hash40(variant),
+            required(value, self.options)?,
+        ))
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        Ok(ListSerializer(Vec::with_capacity(len.unwrap_or_default())))
+        Ok(ListSerializer {
+            list: Vec::with_capacity(len.unwrap_or_default()),
+            options: self.options,
+        })
     }
 
     fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        Ok(ListSerializer(Vec::with_capacity(len)))
+        Ok(ListSerializer {
+            list: Vec::with_capacity(len),
+            options: self.options,
+        })
     }
 
     fn serialize_tuple_struct(
@@ -315,23 +589,33 @@ impl Serializer for IntoValueSerializer {
         _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        Ok(ListSerializer(Vec::with_capacity(len)))
+        Ok(ListSerializer {
+            list: Vec::with_capacity(len),
+            options: self.options,
+        })
     }
 
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        e!("tuple variant")
+        Ok(VariantSerializer {
+            variant: hash40::hash40(variant),
+            inner: ListSerializer {
+                list: Vec::with_capacity(len),
+                options: self.options,
+            },
+        })
     }
 
     fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
         Ok(MapSerializer {
             map: IndexMap::with_capacity(len.unwrap_or_default()),
             current_key: None,
+            options: self.options,
         })
     }
 
@@ -343,6 +627,7 @@ impl Serializer for IntoValueSerializer {
         Ok(MapSerializer {
             map: IndexMap::with_capacity(len),
             current_key: None,
+            options: self.options,
         })
     }
 
@@ -350,10 +635,17 @@ impl Serializer for IntoValueSerializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        e!("struct variant")
+        Ok(VariantSerializer {
+            variant: hash40::hash40(variant),
+            inner: MapSerializer {
+                map: IndexMap::with_capacity(len),
+                current_key: None,
+                options: self.options,
+            },
+        })
     }
 }
 
@@ -362,42 +654,85 @@ impl Serialize for Value {
     where
         S: serde::Serializer,
     {
-        match self {
-            Self::Bool(v) => serializer.serialize_bool(*v),
-            Self::I8(v) => serializer.serialize_i8(*v),
-            Self::U8(v) => serializer.serialize_u8(*v),
-            Self::I16(v) => serializer.serialize_i16(*v),
-            Self::U16(v) => serializer.serialize_u16(*v),
-            Self::I32(v) => serializer.serialize_i32(*v),
-            Self::U32(v) => serializer.serialize_u32(*v),
-            Self::F32(v) => serializer.serialize_f32(*v),
-            Self::Hash(v) => {
-                if serializer.is_human_readable() {
-                    v.serialize(serializer)
-                } else {
-                    v.0.serialize(serializer)
-                }
+        serialize_value(self, false, serializer)
+    }
+}
+
+/// Serializes a [`Value`] so that its strings can be told apart from hashes in human readable
+/// formats, which write both as strings. Any string which looks like a `0x0123456789` hex literal,
+/// or starts with a `\`, gets a leading `\`. Read it back with [`Strings::Escaped`].
+///
+/// ```
+/// # use serde_prc::{ser::Escaped, Value};
+/// let value = Value::List(vec![Value::String("0x0123456789".into())]);
+/// let json = serde_json::to_string(&Escaped(&value)).unwrap();
+/// assert_eq!(json, r#"["\\0x0123456789"]"#);
+/// ```
+///
+/// [`Strings::Escaped`]: crate::de::Strings::Escaped
+pub struct Escaped<'a>(pub &'a Value);
+
+impl Serialize for Escaped<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serialize_value(self.0, true, serializer)
+    }
+}
+
+fn serialize_value<S>(value: &Value, escape: bool, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match value {
+        Value::Bool(v) => serializer.serialize_bool(*v),
+        Value::I8(v) => serializer.serialize_i8(*v),
+        Value::U8(v) => serializer.serialize_u8(*v),
+        Value::I16(v) => serializer.serialize_i16(*v),
+        Value::U16(v) => serializer.serialize_u16(*v),
+        Value::I32(v) => serializer.serialize_i32(*v),
+        Value::U32(v) => serializer.serialize_u32(*v),
+        Value::F32(v) => serializer.serialize_f32(*v),
+        Value::Hash(v) => {
+            if serializer.is_human_readable() {
+                v.serialize(serializer)
+            } else {
+                HashParam(*v).serialize(serializer)
             }
-            Self::String(v) => serializer.serialize_str(v),
-            Self::List(v) => {
-                let mut seq = serializer.serialize_seq(Some(v.len()))?;
-                for value in v.iter() {
+        }
+        Value::String(v) => {
+            if escape
+                && serializer.is_human_readable()
+                && (v.starts_with('\\') || crate::hash_from_hex_literal(v).is_some())
+            {
+                serializer.serialize_str(&format!("\\{v}"))
+            } else {
+                serializer.serialize_str(v)
+            }
+        }
+        Value::List(v) => {
+            let mut seq = serializer.serialize_seq(Some(v.len()))?;
+            for value in v.iter() {
+                if escape {
+                    seq.serialize_element(&Escaped(value))?;
+                } else {
                     seq.serialize_element(value)?;
                 }
-                seq.end()
             }
-            Self::Map(v) => {
-                let is_human = serializer.is_human_readable();
-                let mut map = serializer.serialize_map(Some(v.len()))?;
-                for (k, v) in v.iter() {
-                    if is_human {
-                        map.serialize_entry(k, v)?;
-                    } else {
-                        map.serialize_entry(&k.0, v)?;
-                    }
+            seq.end()
+        }
+        Value::Map(v) => {
+            let is_human = serializer.is_human_readable();
+            let mut map = serializer.serialize_map(Some(v.len()))?;
+            for (k, v) in v.iter() {
+                match (is_human, escape) {
+                    (true, true) => map.serialize_entry(k, &Escaped(v))?,
+                    (true, false) => map.serialize_entry(k, v)?,
+                    (false, _) => map.serialize_entry(&k.0, v)?,
                 }
-                map.end()
             }
+            map.end()
         }
     }
 }
@@ -471,7 +806,9 @@ impl Serializer for HashSerializer {
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-        Ok(hash40::hash40(v))
+        // Keys come back from the deserializer as labels or hex literals, so undo that here
+        // instead of hashing them again
+        crate::hash_from_label(v).ok_or_else(|| Error::UnknownLabel(v.to_string()))
     }
 
     fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok, Self::Error> {
@@ -482,9 +819,9 @@ impl Serializer for HashSerializer {
         key_err!("none")
     }
 
-    fn serialize_some<T: ?Sized>(self, _value: &T) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<T>(self, _value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         key_err!("some")
     }
@@ -506,18 +843,18 @@ impl Serializer for HashSerializer {
         Ok(hash40::hash40(variant))
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
         _name: &'static str,
-        _value: &T,
+        value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        key_err!("newtype struct")
+        value.serialize(self)
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -525,7 +862,7 @@ impl Serializer for HashSerializer {
         _value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         key_err!("newtype variant")
     }
@@ -579,33 +916,152 @@ impl Serializer for HashSerializer {
     }
 }
 
-const fn prim<T: Sized>() -> usize {
+pub(crate) const fn prim<T: Sized>() -> usize {
     1 + std::mem::size_of::<T>()
 }
 
-fn calculate_binary_size_of_value(value: &Value) -> usize {
+#[cfg(test)]
+thread_local! {
+    /// Makes every map produce the same struct key, so that tests can check that colliding keys
+    /// never cause differently shaped maps to share a struct table
+    pub(crate) static FORCE_STRUCT_KEY_COLLISIONS: std::cell::Cell<bool> =
+        const { std::cell::Cell::new(false) };
+}
+
+/// The encoded size of a param which isn't a list or a map
+fn leaf_size(value: &Value) -> usize {
     match value {
         Value::Bool(_) | Value::U8(_) | Value::I8(_) => prim::<u8>(),
         Value::I16(_) | Value::U16(_) => prim::<u16>(),
         Value::I32(_) | Value::U32(_) | Value::F32(_) | Value::String(_) | Value::Hash(_) => {
             prim::<u32>()
         }
-        Value::List(values) => {
-            prim::<u32>()
-                + values.len() * std::mem::size_of::<u32>()
-                + values
+        Value::List(_) | Value::Map(_) => unreachable!("lists and maps are measured separately"),
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Node {
+    /// The encoded size of the param, including everything below it
+    size: usize,
+
+    /// How many params are below this one
+    descendants: usize,
+
+    /// A digest of the layout of a map param, used to find struct tables which may be shareable.
+    /// Maps with different layouts can produce the same key, so a candidate table still has to
+    /// be compared against the real one.
+    struct_key: u64,
+}
+
+/// The encoded size and struct key of every param in a value, in the order that they are
+/// written. These are computed in a single bottom-up pass so that writing never has to walk a
+/// subtree more than once.
+pub(crate) struct ParamSizes {
+    nodes: Vec<Node>,
+}
+
+impl ParamSizes {
+    pub fn new(value: &Value) -> Self {
+        let mut nodes = Vec::new();
+        Self::measure(&mut nodes, value);
+        Self { nodes }
+    }
+
+    fn measure(nodes: &mut Vec<Node>, value: &Value) -> usize {
+        use std::hash::Hash;
+
+        let index = nodes.len();
+        nodes.push(Node {
+            size: 0,
+            descendants: 0,
+            struct_key: 0,
+        });
+
+        let (size, struct_key) = match value {
+            Value::List(values) => {
+                let children = values
                     .iter()
-                    .map(calculate_binary_size_of_value)
-                    .sum::<usize>()
-        }
-        Value::Map(map) => {
-            prim::<u32>()
-                + std::mem::size_of::<u32>()
-                + map
-                    .values()
-                    .map(calculate_binary_size_of_value)
-                    .sum::<usize>()
+                    .map(|value| Self::measure(nodes, value))
+                    .sum::<usize>();
+
+                (
+                    prim::<u32>() + values.len() * std::mem::size_of::<u32>() + children,
+                    0,
+                )
+            }
+            Value::Map(map) => {
+                let mut size = prim::<u32>() + std::mem::size_of::<u32>();
+                let mut hasher = DefaultHasher::default();
+                for (key, value) in map.iter() {
+                    // Only the offsets end up in the struct table, not the size of the last value
+                    key.hash(&mut hasher);
+                    size.hash(&mut hasher);
+                    size += Self::measure(nodes, value);
+                }
+
+                #[cfg(test)]
+                if FORCE_STRUCT_KEY_COLLISIONS.with(|force| force.get()) {
+                    hasher = DefaultHasher::default();
+                }
+
+                (size, hasher.finish())
+            }
+            _ => (leaf_size(value), 0),
+        };
+
+        nodes[index] = Node {
+            size,
+            descendants: nodes.len() - index - 1,
+            struct_key,
+        };
+
+        size
+    }
+
+    /// The encoded size of the param at `index`
+    pub fn size(&self, index: usize) -> usize {
+        self.nodes[index].size
+    }
+
+    fn struct_key(&self, index: usize) -> u64 {
+        self.nodes[index].struct_key
+    }
+
+    /// The indices of the params directly below the param at `index`
+    pub fn children(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let end = index + 1 + self.nodes[index].descendants;
+        let mut next = index + 1;
+        std::iter::from_fn(move || {
+            let child = next;
+            (child < end).then(|| {
+                next += 1 + self.nodes[child].descendants;
+                child
+            })
+        })
+    }
+
+    /// Builds the struct table of the map param at `index`, with `hash_index` giving the index of
+    /// each key in the hash table
+    pub fn struct_table(
+        &self,
+        index: usize,
+        map: &IndexMap<Hash40, Value>,
+        mut hash_index: impl FnMut(&Hash40) -> u32,
+    ) -> Vec<u8> {
+        let mut table = Vec::with_capacity(map.len() * 8);
+        let mut wip_offset = prim::<u32>() + std::mem::size_of::<u32>();
+        for (key, child) in map.keys().zip(self.children(index)) {
+            table
+                .write_u32::<LittleEndian>(hash_index(key))
+                .expect("writing to vec");
+            table
+                .write_u32::<LittleEndian>(wip_offset as u32)
+                .expect("writing to vec");
+            wip_offset += self.size(child);
         }
+
+        table
     }
 }
 
@@ -627,83 +1083,191 @@ fn visit_hashes(lookup: &mut IndexSet<Hash40>, value: &Value) {
     }
 }
 
-fn visit_strings(data: &mut Vec<u8>, lookup: &mut HashMap<String, u32>, value: &Value) {
+/// The offsets of the strings and struct tables in the reference data, in the order that the
+/// params pointing at them are written
+#[derive(Default)]
+struct ReferenceOffsets {
+    strings: VecDeque<u32>,
+    structs: VecDeque<u32>,
+}
+
+fn visit_strings(
+    data: &mut Vec<u8>,
+    lookup: &mut HashMap<String, u32>,
+    offsets: &mut ReferenceOffsets,
+    options: Options,
+    value: &Value,
+) {
     match value {
-        Value::String(string) if !lookup.contains_key(string) => {
-            let offset = data.len() as u32;
-            data.extend_from_slice(string.as_bytes());
-            data.push(b'\0');
-            lookup.insert(string.clone(), offset);
+        Value::String(string) => {
+            let offset = match lookup.get(string) {
+                Some(offset) if options.dedup_strings => *offset,
+                _ => {
+                    let offset = data.len() as u32;
+                    data.extend_from_slice(string.as_bytes());
+                    data.push(b'\0');
+                    lookup.insert(string.clone(), offset);
+                    offset
+                }
+            };
+
+            offsets.strings.push_back(offset);
         }
         Value::List(values) => values
             .iter()
-            .for_each(|value| visit_strings(data, lookup, value)),
+            .for_each(|value| visit_strings(data, lookup, offsets, options, value)),
         Value::Map(map) => map
             .values()
-            .for_each(|value| visit_strings(data, lookup, value)),
+            .for_each(|value| visit_strings(data, lookup, offsets, options, value)),
         _ => {}
     }
 }
 
-fn get_struct_key(map: &IndexMap<Hash40, Value>) -> u64 {
-    use std::hash::Hash;
-    let mut hasher = DefaultHasher::default();
-    for (key, value) in map.iter() {
-        key.hash(&mut hasher);
-        calculate_binary_size_of_value(value).hash(&mut hasher);
+/// Lays out the struct tables in the reference data, sharing them between maps with the same
+/// layout if allowed
+struct StructTables<'a> {
+    hashes: &'a IndexSet<Hash40>,
+    sizes: &'a ParamSizes,
+    share: bool,
+    lookup: HashMap<u64, Vec<u32>>,
+}
+
+impl StructTables<'_> {
+    fn visit(
+        &mut self,
+        data: &mut Vec<u8>,
+        offsets: &mut ReferenceOffsets,
+        index: usize,
+        value: &Value,
+    ) {
+        let sizes = self.sizes;
+        match value {
+            Value::List(list) => {
+                for (child, value) in sizes.children(index).zip(list.iter()) {
+                    self.visit(data, offsets, child, value);
+                }
+            }
+            Value::Map(map) => {
+                let table = sizes.struct_table(index, map, |key| {
+                    self.hashes
+                        .get_index_of(key)
+                        .expect("should have cached the map key") as u32
+                });
+
+                let candidates = self.lookup.entry(sizes.struct_key(index)).or_default();
+                let shared = candidates.iter().copied().find(|offset| {
+                    let start = *offset as usize;
+                    self.share && data.get(start..start + table.len()) == Some(&table[..])
+                });
+
+                let offset = match shared {
+                    Some(offset) => offset,
+                    None => {
+                        let offset = data.len() as u32;
+                        data.extend_from_slice(&table);
+                        candidates.push(offset);
+                        offset
+                    }
+                };
+
+                offsets.structs.push_back(offset);
+
+                for (child, value) in sizes.children(index).zip(map.values()) {
+                    self.visit(data, offsets, child, value);
+                }
+            }
+            _ => {}
+        }
     }
+}
+
+/// Resolves what the params in the body point at while it is being written
+pub(crate) trait References {
+    /// The index of `hash` in the hash table
+    fn hash_index(&mut self, hash: Hash40) -> u32;
 
-    hasher.finish()
+    /// The offset into the reference data of a string param's text or a map param's struct
+    /// table. This is called in the same order that the params are written in.
+    fn reference_offset(&mut self, value: &Value) -> u32;
 }
 
-fn visit_structs(
-    hashes: &IndexSet<Hash40>,
-    data: &mut Vec<u8>,
-    lookup: &mut HashMap<u64, u32>,
+struct Lookups<'a> {
+    hashes: &'a IndexSet<Hash40>,
+    offsets: ReferenceOffsets,
+}
+
+impl References for Lookups<'_> {
+    fn hash_index(&mut self, hash: Hash40) -> u32 {
+        self.hashes
+            .get_index_of(&hash)
+            .expect("should have cached hash") as u32
+    }
+
+    fn reference_offset(&mut self, value: &Value) -> u32 {
+        match value {
+            Value::String(_) => self
+                .offsets
+                .strings
+                .pop_front()
+                .expect("should have cached string"),
+            Value::Map(_) => self
+                .offsets
+                .structs
+                .pop_front()
+                .expect("should have cached struct"),
+            _ => unreachable!("only strings and maps point into the reference data"),
+        }
+    }
+}
+
+/// Writes the body of the file, with `sizes` measured from `value`
+pub(crate) fn write_value<W: Write, R: References>(
+    writer: &mut W,
+    references: &mut R,
+    sizes: &ParamSizes,
     value: &Value,
-) {
+) -> Result<(), Error> {
+    write_param(writer, references, sizes, 0, value)
+}
+
+fn write_param<W: Write, R: References>(
+    writer: &mut W,
+    references: &mut R,
+    sizes: &ParamSizes,
+    index: usize,
+    value: &Value,
+) -> Result<(), Error> {
     match value {
-        Value::List(list) => {
-            for value in list.iter() {
-                visit_structs(hashes, data, lookup, value);
+        Value::List(v) => {
+            writer.write_u8(ParamId::List as u8)?;
+            writer.write_u32::<LittleEndian>(v.len() as u32)?;
+            let mut wip_offset = prim::<u32>() + v.len() * std::mem::size_of::<u32>();
+            for child in sizes.children(index) {
+                writer.write_u32::<LittleEndian>(wip_offset as u32)?;
+                wip_offset += sizes.size(child);
+            }
+            for (child, value) in sizes.children(index).zip(v.iter()) {
+                write_param(writer, references, sizes, child, value)?;
             }
         }
         Value::Map(map) => {
-            let key = get_struct_key(map);
-
-            if lookup.contains_key(&key) {
-                return;
-            }
-
-            let ref_offset = data.len() as u32;
-            let mut wip_offset = prim::<u32>() + std::mem::size_of::<u32>();
-            for (key, value) in map.iter() {
-                let key_index = hashes
-                    .get_index_of(key)
-                    .expect("should have cached the map key");
-                let value_offset = wip_offset;
-                wip_offset += calculate_binary_size_of_value(value);
-                data.write_u32::<LittleEndian>(key_index as u32)
-                    .expect("writing to vec");
-                data.write_u32::<LittleEndian>(value_offset as u32)
-                    .expect("writing to vec");
-            }
-
-            lookup.insert(key, ref_offset);
-
-            for value in map.values() {
-                visit_structs(hashes, data, lookup, value);
+            writer.write_u8(ParamId::Map as u8)?;
+            writer.write_u32::<LittleEndian>(map.len() as u32)?;
+            writer.write_u32::<LittleEndian>(references.reference_offset(value))?;
+            for (child, value) in sizes.children(index).zip(map.values()) {
+                write_param(writer, references, sizes, child, value)?;
             }
         }
-        _ => {}
+        _ => write_leaf(writer, references, value)?,
     }
+
+    Ok(())
 }
 
-fn write_value<W: Write>(
+/// Writes a param which isn't a list or a map
+fn write_leaf<W: Write, R: References>(
     writer: &mut W,
-    hashes: &IndexSet<Hash40>,
-    strings: &HashMap<String, u32>,
-    structs: &HashMap<u64, u32>,
+    references: &mut R,
     value: &Value,
 ) -> Result<(), Error> {
     match value {
@@ -741,59 +1305,52 @@ fn write_value<W: Write>(
         }
         Value::Hash(v) => {
             writer.write_u8(ParamId::Hash as u8)?;
-            writer.write_u32::<LittleEndian>(
-                hashes.get_index_of(v).expect("should have cached hash") as u32,
-            )?;
+            writer.write_u32::<LittleEndian>(references.hash_index(*v))?;
         }
-        Value::String(v) => {
+        Value::String(_) => {
             writer.write_u8(ParamId::String as u8)?;
-            writer
-                .write_u32::<LittleEndian>(*strings.get(v).expect("should have cached string"))?;
-        }
-        Value::List(v) => {
-            writer.write_u8(ParamId::List as u8)?;
-            writer.write_u32::<LittleEndian>(v.len() as u32)?;
-            let mut wip_offset = (prim::<u32>() + v.len() * std::mem::size_of::<u32>()) as u32;
-            for value in v.iter() {
-                writer.write_u32::<LittleEndian>(wip_offset)?;
-                wip_offset += calculate_binary_size_of_value(value) as u32;
-            }
-            for value in v.iter() {
-                write_value(writer, hashes, strings, structs, value)?;
-            }
-        }
-        Value::Map(map) => {
-            writer.write_u8(ParamId::Map as u8)?;
-            writer.write_u32::<LittleEndian>(map.len() as u32)?;
-            writer.write_u32::<LittleEndian>(
-                *structs
-                    .get(&get_struct_key(map))
-                    .expect("should have cached struct"),
-            )?;
-            for value in map.values() {
-                write_value(writer, hashes, strings, structs, value)?;
-            }
+            writer.write_u32::<LittleEndian>(references.reference_offset(value))?;
         }
+        Value::List(_) | Value::Map(_) => unreachable!("lists and maps are written separately"),
     }
 
     Ok(())
 }
 
-pub fn write<W: Write, T: Serialize>(mut writer: W, value: &T) -> Result<(), Error> {
-    let value = value.serialize(IntoValueSerializer)?;
+pub fn write<W: Write, T: Serialize>(writer: W, value: &T) -> Result<(), Error> {
+    write_with(writer, value, Options::default())
+}
+
+pub fn write_with<W: Write, T: Serialize>(
+    mut writer: W,
+    value: &T,
+    options: Options,
+) -> Result<(), Error> {
+    let value = required(value, options)?;
+    let sizes = ParamSizes::new(&value);
 
     let mut hash_lookup = IndexSet::with_capacity(64);
     let mut reference_data = Vec::with_capacity(128);
-    let mut string_lookup = HashMap::new();
-    let mut struct_lookup = HashMap::new();
+    let mut offsets = ReferenceOffsets::default();
     visit_hashes(&mut hash_lookup, &value);
-    visit_strings(&mut reference_data, &mut string_lookup, &value);
-    visit_structs(
-        &hash_lookup,
+    if options.hash_order == HashOrder::Sorted {
+        hash_lookup.sort();
+    }
+
+    visit_strings(
         &mut reference_data,
-        &mut struct_lookup,
+        &mut HashMap::new(),
+        &mut offsets,
+        options,
         &value,
     );
+    StructTables {
+        hashes: &hash_lookup,
+        sizes: &sizes,
+        share: options.share_structs,
+        lookup: HashMap::new(),
+    }
+    .visit(&mut reference_data, &mut offsets, 0, &value);
     writer.write_all(b"paracobn")?;
 
     writer.write_u32::<LittleEndian>(8 * hash_lookup.len() as u32)?;
@@ -807,9 +1364,11 @@ pub fn write<W: Write, T: Serialize>(mut writer: W, value: &T) -> Result<(), Err
 
     write_value(
         &mut writer,
-        &hash_lookup,
-        &string_lookup,
-        &struct_lookup,
+        &mut Lookups {
+            hashes: &hash_lookup,
+            offsets,
+        },
+        &sizes,
         &value,
     )?;
 
@@ -817,8 +1376,12 @@ pub fn write<W: Write, T: Serialize>(mut writer: W, value: &T) -> Result<(), Err
 }
 
 pub fn to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    to_vec_with(value, Options::default())
+}
+
+pub fn to_vec_with<T: Serialize>(value: &T, options: Options) -> Result<Vec<u8>, Error> {
     let mut writer = Cursor::new(Vec::with_capacity(256));
-    write(&mut writer, value)?;
+    write_with(&mut writer, value, options)?;
 
     Ok(writer.into_inner())
 }
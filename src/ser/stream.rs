@@ -0,0 +1,879 @@
+//! Serialization straight into a file, without converting the value into a [`Value`] first.
+//!
+//! The value is serialized once, writing each param to the body as soon as it is seen while the
+//! hash table and the reference data are built up in memory. The body starts after a region
+//! which is set aside for them up front, see [`Options::streaming_reserve`]. Whatever isn't
+//! known yet when a param is written is filled in later by seeking back:
+//!
+//! - the entry count and offsets of a list are written when the list ends. A list which doesn't
+//!   give its length up front is held in memory until then, since its elements can't be written
+//!   before their offsets.
+//! - the entry count and struct table of a map are written once the reference data is complete,
+//!   along with the index of every hash param if the hash table is sorted.
+//! - finally the header, the hash table and the reference data are written into the region in
+//!   front of the body, padding the reference data with zeros to fill it.
+
+use std::{
+    collections::HashMap,
+    io::{Seek, SeekFrom, Write},
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use hash40::Hash40;
+use indexmap::IndexSet;
+use serde::{
+    ser::{
+        SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+    Serialize, Serializer,
+};
+
+use super::{
+    prim, write_leaf, Error, HashOrder, HashSerializer, IntoValueSerializer, Options, References,
+    HASH_NEWTYPE,
+};
+use crate::{ParamId, Value};
+
+/// The magic and the sizes of the hash table and reference data
+const HEADER_SIZE: u64 = 0x10;
+
+/// Anything the file can be written to
+trait Sink: Write + Seek {}
+
+impl<T: Write + Seek> Sink for T {}
+
+/// Serializes a value which can't be left out, such as a list element
+fn required<W: Sink, T: ?Sized + Serialize>(
+    stream: &mut Stream<W>,
+    options: Options,
+    value: &T,
+) -> Result<(), Error> {
+    value
+        .serialize(StreamSerializer { stream, options })
+        .map_err(|error| match error {
+            Error::UnsupportedNone => Error::UnsupportedValueType("none"),
+            error => error,
+        })
+}
+
+/// Serializes a map entry, leaving it out if the value is `None`
+fn entry<W: Sink, T: ?Sized + Serialize>(
+    stream: &mut Stream<W>,
+    options: Options,
+    map: &mut StreamMap,
+    key: Hash40,
+    value: &T,
+) -> Result<(), Error> {
+    stream.begin_entry(map, key);
+    let present = match value.serialize(StreamSerializer {
+        stream: &mut *stream,
+        options,
+    }) {
+        Ok(()) => true,
+        Err(Error::UnsupportedNone) => false,
+        Err(error) => return Err(error),
+    };
+
+    stream.end_entry(map, key, present);
+    Ok(())
+}
+
+struct StreamSerializer<'a, W> {
+    stream: &'a mut Stream<W>,
+    options: Options,
+}
+
+struct ListStream<'a, W: Sink> {
+    stream: &'a mut Stream<W>,
+    options: Options,
+    list: StreamList,
+}
+
+impl<'a, W: Sink> ListStream<'a, W> {
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.stream.begin_element(&mut self.list);
+        required(self.stream, self.options, value)
+    }
+
+    fn finish(self) -> Result<&'a mut Stream<W>, Error> {
+        self.stream.end_list(self.list)?;
+        Ok(self.stream)
+    }
+}
+
+struct MapStream<'a, W: Sink> {
+    stream: &'a mut Stream<W>,
+    options: Options,
+    map: StreamMap,
+    current_key: Option<Hash40>,
+}
+
+impl<'a, W: Sink> MapStream<'a, W> {
+    fn field<T: ?Sized + Serialize>(&mut self, key: Hash40, value: &T) -> Result<(), Error> {
+        entry(self.stream, self.options, &mut self.map, key, value)
+    }
+
+    fn finish(self) -> Result<&'a mut Stream<W>, Error> {
+        self.stream.end_map(self.map)?;
+        Ok(self.stream)
+    }
+}
+
+/// Serializes the data of an enum variant inside of a single-entry map keyed by the variant name
+struct VariantStream<S> {
+    map: StreamMap,
+    variant: Hash40,
+    inner: S,
+}
+
+impl<'a, W: Sink> VariantStream<ListStream<'a, W>> {
+    fn finish(mut self) -> Result<(), Error> {
+        let stream = self.inner.finish()?;
+        stream.end_entry(&mut self.map, self.variant, true);
+        stream.end_map(self.map)
+    }
+}
+
+impl<'a, W: Sink> VariantStream<MapStream<'a, W>> {
+    fn finish(mut self) -> Result<(), Error> {
+        let stream = self.inner.finish()?;
+        stream.end_entry(&mut self.map, self.variant, true);
+        stream.end_map(self.map)
+    }
+}
+
+impl<W: Sink> SerializeSeq for ListStream<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish().map(|_| ())
+    }
+}
+
+impl<W: Sink> SerializeTuple for ListStream<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish().map(|_| ())
+    }
+}
+
+impl<W: Sink> SerializeTupleStruct for ListStream<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish().map(|_| ())
+    }
+}
+
+impl<W: Sink> SerializeMap for MapStream<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.current_key = Some(key.serialize(HashSerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let Some(key) = self.current_key.take() else {
+            return Err(Error::Custom(
+                "attempting to serialize value with no key".to_string(),
+            ));
+        };
+
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish().map(|_| ())
+    }
+}
+
+impl<W: Sink> SerializeStruct for MapStream<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.field(hash40::hash40(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish().map(|_| ())
+    }
+}
+
+impl<'a, W: Sink> SerializeTupleVariant for VariantStream<ListStream<'a, W>> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.element(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Sink> SerializeStructVariant for VariantStream<MapStream<'a, W>> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.field(hash40::hash40(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+/// Converts the value with [`IntoValueSerializer`] so that both serializers agree on which param
+/// each type becomes
+macro_rules! leaves {
+    ($($method:ident($($arg:ident: $ty:ty),*)),*) => {
+        $(
+            fn $method(self, $($arg: $ty),*) -> Result<Self::Ok, Self::Error> {
+                let value = IntoValueSerializer::new(self.options).$method($($arg),*);
+                self.stream.leaf(&value?)
+            }
+        )*
+    };
+}
+
+impl<'a, W: Sink> StreamSerializer<'a, W> {
+    fn list(self, len: Option<usize>) -> Result<ListStream<'a, W>, Error> {
+        let list = self.stream.begin_list(len)?;
+        Ok(ListStream {
+            stream: self.stream,
+            options: self.options,
+            list,
+        })
+    }
+
+    fn map(self) -> Result<MapStream<'a, W>, Error> {
+        let map = self.stream.begin_map()?;
+        Ok(MapStream {
+            stream: self.stream,
+            options: self.options,
+            map,
+            current_key: None,
+        })
+    }
+
+    /// Starts the single-entry map holding the data of an enum variant
+    fn variant(&mut self, variant: &'static str) -> Result<(StreamMap, Hash40), Error> {
+        let variant = hash40::hash40(variant);
+        let mut map = self.stream.begin_map()?;
+        self.stream.begin_entry(&mut map, variant);
+        Ok((map, variant))
+    }
+}
+
+impl<'a, W: Sink> Serializer for StreamSerializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = ListStream<'a, W>;
+
+    type SerializeTuple = ListStream<'a, W>;
+
+    type SerializeTupleStruct = ListStream<'a, W>;
+
+    type SerializeTupleVariant = VariantStream<ListStream<'a, W>>;
+
+    type SerializeMap = MapStream<'a, W>;
+
+    type SerializeStruct = MapStream<'a, W>;
+
+    type SerializeStructVariant = VariantStream<MapStream<'a, W>>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    leaves! {
+        serialize_bool(v: bool),
+        serialize_i8(v: i8),
+        serialize_u8(v: u8),
+        serialize_i16(v: i16),
+        serialize_u16(v: u16),
+        serialize_i32(v: i32),
+        serialize_u32(v: u32),
+        serialize_i64(v: i64),
+        serialize_u64(v: u64),
+        serialize_f32(v: f32),
+        serialize_f64(v: f64),
+        serialize_char(v: char),
+        serialize_str(v: &str),
+        serialize_bytes(v: &[u8]),
+        serialize_none(),
+        serialize_unit(),
+        serialize_unit_struct(name: &'static str),
+        serialize_unit_variant(name: &'static str, variant_index: u32, variant: &'static str)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if name == HASH_NEWTYPE {
+            return self
+                .stream
+                .leaf(&Value::Hash(value.serialize(HashSerializer)?));
+        }
+
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        mut self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let (mut map, variant) = self.variant(variant)?;
+        required(self.stream, self.options, value)?;
+        self.stream.end_entry(&mut map, variant, true);
+        self.stream.end_map(map)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        self.list(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.list(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.list(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        mut self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        let (map, variant) = self.variant(variant)?;
+        Ok(VariantStream {
+            map,
+            variant,
+            inner: self.list(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        self.map()
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        self.map()
+    }
+
+    fn serialize_struct_variant(
+        mut self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        let (map, variant) = self.variant(variant)?;
+        Ok(VariantStream {
+            map,
+            variant,
+            inner: self.map()?,
+        })
+    }
+}
+
+/// The body of the file. Lists which are held in memory are written out once they end, and
+/// everything inside of them goes to memory in the meantime.
+struct Body<W> {
+    inner: W,
+
+    /// The position in the file that the body has reached, counting what is held in memory
+    position: u64,
+
+    /// The position of `inner`, which stays put while anything is held in memory
+    written: u64,
+
+    /// Where each held list starts and what has been written since, from the outermost one
+    held: Vec<(u64, Vec<u8>)>,
+}
+
+impl<W: Write> Write for Body<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = match self.held.last_mut() {
+            Some((_, bytes)) => {
+                bytes.extend_from_slice(buf);
+                buf.len()
+            }
+            None => {
+                let len = self.inner.write(buf)?;
+                self.written += len as u64;
+                len
+            }
+        };
+
+        self.position += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Sink> Body<W> {
+    /// Overwrites bytes which were already written, wherever they are now
+    fn patch(&mut self, position: u64, bytes: &[u8]) -> std::io::Result<()> {
+        match self
+            .held
+            .iter_mut()
+            .rev()
+            .find(|(start, _)| *start <= position)
+        {
+            Some((start, held)) => {
+                let at = (position - *start) as usize;
+                held[at..at + bytes.len()].copy_from_slice(bytes);
+            }
+            None => {
+                self.inner.seek(SeekFrom::Start(position))?;
+                self.inner.write_all(bytes)?;
+                self.inner.seek(SeekFrom::Start(self.written))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Holds everything written from here on in memory until [`release`](Self::release)
+    fn hold(&mut self) {
+        self.held.push((self.position, Vec::new()));
+    }
+
+    /// Stops holding the last held bytes and rewinds to where they started, so that they can be
+    /// written again with something inserted
+    fn release(&mut self) -> Vec<u8> {
+        let (start, bytes) = self.held.pop().expect("should be holding something");
+        self.position = start;
+        bytes
+    }
+}
+
+/// The single reference of the param being written, which is already known
+struct Resolved(u32);
+
+impl References for Resolved {
+    fn hash_index(&mut self, _hash: Hash40) -> u32 {
+        self.0
+    }
+
+    fn reference_offset(&mut self, _value: &Value) -> u32 {
+        self.0
+    }
+}
+
+/// The file being written, along with everything that goes in front of the body
+struct Stream<W> {
+    writer: Body<W>,
+    options: Options,
+
+    /// Where the file starts in the writer
+    start: u64,
+
+    hashes: IndexSet<Hash40>,
+    strings: Vec<u8>,
+    string_lookup: HashMap<String, u32>,
+    tables: Vec<Vec<(Hash40, u32)>>,
+    table_lookup: HashMap<Vec<(Hash40, u32)>, usize>,
+
+    /// The position and struct table of every map, in the order that the maps start. The table
+    /// is `None` until the map ends.
+    maps: Vec<(u64, Option<usize>)>,
+
+    /// The position of every hash param, which is only kept when the hash table gets sorted
+    hash_params: Vec<(u64, Hash40)>,
+}
+
+struct StreamList {
+    start: u64,
+
+    /// How many offsets were written in front of the elements, or `None` if the list is held
+    /// in memory because its length wasn't given
+    reserved: Option<u32>,
+    offsets: Vec<u32>,
+}
+
+struct StreamMap {
+    index: usize,
+    start: u64,
+    entries: Vec<(Hash40, u32)>,
+    value_start: u64,
+    new_key: bool,
+}
+
+impl<W: Sink> Stream<W> {
+    fn new(mut writer: W, options: Options) -> Result<Self, Error> {
+        let start = writer.stream_position()?;
+
+        // The header, hash table and reference data are written in front of the body at the end
+        let body = start + HEADER_SIZE + options.streaming_reserve as u64;
+        writer.seek(SeekFrom::Start(body))?;
+
+        Ok(Self {
+            writer: Body {
+                inner: writer,
+                position: body,
+                written: body,
+                held: Vec::new(),
+            },
+            options,
+            start,
+            hashes: IndexSet::new(),
+            strings: Vec::new(),
+            string_lookup: HashMap::new(),
+            tables: Vec::new(),
+            table_lookup: HashMap::new(),
+            maps: Vec::new(),
+            hash_params: Vec::new(),
+        })
+    }
+
+    fn string_offset(&mut self, string: &str) -> u32 {
+        match self.string_lookup.get(string) {
+            Some(offset) if self.options.dedup_strings => *offset,
+            _ => {
+                let offset = self.strings.len() as u32;
+                self.strings.extend_from_slice(string.as_bytes());
+                self.strings.push(b'\0');
+                self.string_lookup.insert(string.to_string(), offset);
+                offset
+            }
+        }
+    }
+
+    /// Handles any param which isn't a list or a map
+    fn leaf(&mut self, value: &Value) -> Result<(), Error> {
+        let reference = match value {
+            Value::Hash(hash) => {
+                if self.options.hash_order == HashOrder::Sorted {
+                    self.hash_params.push((self.writer.position, *hash));
+                }
+
+                self.hashes.insert_full(*hash).0 as u32
+            }
+            Value::String(string) => self.string_offset(string),
+            _ => 0,
+        };
+
+        write_leaf(&mut self.writer, &mut Resolved(reference), value)
+    }
+
+    fn begin_list(&mut self, len: Option<usize>) -> Result<StreamList, Error> {
+        let start = self.writer.position;
+        let reserved = len.map(|len| len as u32);
+        if reserved.is_none() {
+            self.writer.hold();
+        }
+
+        self.writer.write_u8(ParamId::List as u8)?;
+        self.writer
+            .write_u32::<LittleEndian>(reserved.unwrap_or(0))?;
+        for _ in 0..reserved.unwrap_or(0) {
+            self.writer.write_u32::<LittleEndian>(0)?;
+        }
+
+        Ok(StreamList {
+            start,
+            reserved,
+            offsets: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn begin_element(&mut self, list: &mut StreamList) {
+        list.offsets
+            .push((self.writer.position - list.start) as u32);
+    }
+
+    fn end_list(&mut self, list: StreamList) -> Result<(), Error> {
+        let len = list.offsets.len() as u32;
+
+        match list.reserved {
+            // If the list has fewer elements than it reserved offsets for, the spare offsets
+            // are left as padding
+            Some(reserved) if len <= reserved => {
+                let mut header = Vec::with_capacity(4 + list.offsets.len() * 4);
+                header.write_u32::<LittleEndian>(len)?;
+                for offset in list.offsets {
+                    header.write_u32::<LittleEndian>(offset)?;
+                }
+
+                self.writer.patch(list.start + 1, &header)?;
+            }
+            Some(reserved) => {
+                return Err(Error::ListTooLong {
+                    hint: reserved as usize,
+                })
+            }
+            None => {
+                // Elements are self-contained, so inserting the offsets in front of them only
+                // changes the positions which are still needed
+                let held = self.writer.release();
+                let elements = list.start + prim::<u32>() as u64;
+                let distance = len * 4;
+                self.moved(elements, distance as u64);
+
+                self.writer.write_u8(ParamId::List as u8)?;
+                self.writer.write_u32::<LittleEndian>(len)?;
+                for offset in list.offsets {
+                    self.writer.write_u32::<LittleEndian>(offset + distance)?;
+                }
+                self.writer.write_all(&held[prim::<u32>()..])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Updates the positions which are still needed after everything from `start` onwards has
+    /// been moved forward by `distance` bytes
+    fn moved(&mut self, start: u64, distance: u64) {
+        // Both are in the order they were written, so only a suffix of each has moved
+        let first = self.maps.partition_point(|(position, _)| *position < start);
+        for (position, _) in &mut self.maps[first..] {
+            *position += distance;
+        }
+
+        let first = self
+            .hash_params
+            .partition_point(|(position, _)| *position < start);
+        for (position, _) in &mut self.hash_params[first..] {
+            *position += distance;
+        }
+    }
+
+    fn begin_map(&mut self) -> Result<StreamMap, Error> {
+        let start = self.writer.position;
+
+        // The entry count and struct table offset are written by `finish`
+        self.writer.write_u8(ParamId::Map as u8)?;
+        self.writer.write_u32::<LittleEndian>(0)?;
+        self.writer.write_u32::<LittleEndian>(0)?;
+        self.maps.push((start, None));
+
+        Ok(StreamMap {
+            index: self.maps.len() - 1,
+            start,
+            entries: Vec::new(),
+            value_start: 0,
+            new_key: false,
+        })
+    }
+
+    fn begin_entry(&mut self, map: &mut StreamMap, key: Hash40) {
+        // The key goes into the hash table before anything in its value, like in `write`
+        map.new_key = self.hashes.insert(key);
+        map.value_start = self.writer.position;
+    }
+
+    /// `present` is false when the value turned out to be `None`, meaning nothing was written
+    /// for it and the entry is left out
+    fn end_entry(&mut self, map: &mut StreamMap, key: Hash40, present: bool) {
+        if present {
+            map.entries
+                .push((key, (map.value_start - map.start) as u32));
+        } else if map.new_key {
+            // A `None` value adds nothing after its key, so the key is still the last hash
+            self.hashes.pop();
+        }
+    }
+
+    fn end_map(&mut self, map: StreamMap) -> Result<(), Error> {
+        let shared = self
+            .options
+            .share_structs
+            .then(|| self.table_lookup.get(&map.entries).copied())
+            .flatten();
+
+        let table_index = match shared {
+            Some(table_index) => table_index,
+            None => {
+                let table_index = self.tables.len();
+                if self.options.share_structs {
+                    self.table_lookup.insert(map.entries.clone(), table_index);
+                }
+
+                self.tables.push(map.entries);
+                table_index
+            }
+        };
+
+        self.maps[map.index].1 = Some(table_index);
+        Ok(())
+    }
+
+    /// Fills in the hash indices and maps, then writes everything in front of the body
+    fn finish(mut self) -> Result<(), Error> {
+        if self.options.hash_order == HashOrder::Sorted {
+            self.hashes.sort();
+            for (position, hash) in self.hash_params {
+                let index = self
+                    .hashes
+                    .get_index_of(&hash)
+                    .expect("should have added the hash");
+                self.writer
+                    .patch(position + 1, &(index as u32).to_le_bytes())?;
+            }
+        }
+
+        // The struct tables go after the strings, in the order that their first map starts
+        let mut reference = self.strings;
+        let mut table_offsets = vec![None; self.tables.len()];
+        for (position, table_index) in self.maps {
+            let table_index = table_index.expect("should have ended every map");
+            let table = &self.tables[table_index];
+            let offset = *table_offsets[table_index].get_or_insert_with(|| {
+                let offset = reference.len() as u32;
+                for (key, value_offset) in table.iter() {
+                    let key_index = self
+                        .hashes
+                        .get_index_of(key)
+                        .expect("should have added the map key");
+                    reference
+                        .write_u32::<LittleEndian>(key_index as u32)
+                        .expect("writing to vec");
+                    reference
+                        .write_u32::<LittleEndian>(*value_offset)
+                        .expect("writing to vec");
+                }
+
+                offset
+            });
+
+            let mut map = [0; 8];
+            map[..4].copy_from_slice(&(table.len() as u32).to_le_bytes());
+            map[4..].copy_from_slice(&offset.to_le_bytes());
+            self.writer.patch(position + 1, &map)?;
+        }
+
+        let hash_table_size = 8 * self.hashes.len();
+        let needed = hash_table_size + reference.len();
+        let reserved = self.options.streaming_reserve as usize;
+        if needed > reserved {
+            return Err(Error::ReserveTooSmall { needed, reserved });
+        }
+        reference.resize(reserved - hash_table_size, 0);
+
+        let inner = &mut self.writer.inner;
+        inner.seek(SeekFrom::Start(self.start))?;
+        inner.write_all(b"paracobn")?;
+        inner.write_u32::<LittleEndian>(hash_table_size as u32)?;
+        inner.write_u32::<LittleEndian>(reference.len() as u32)?;
+        for hash in self.hashes.iter() {
+            inner.write_u64::<LittleEndian>(hash.0)?;
+        }
+        inner.write_all(&reference)?;
+        inner.seek(SeekFrom::Start(self.writer.written))?;
+
+        Ok(())
+    }
+}
+
+/// Serializes `value` straight into `writer` without building a [`Value`] first.
+///
+/// This produces the same params as [`write`](super::write), but only keeps the hash table, the
+/// reference data, the offsets of unfinished lists and the position of every map in memory.
+/// `value` is only serialized once, so its `Serialize` impl doesn't need to be deterministic.
+///
+/// The hash table and reference data are written into space which is reserved in front of the
+/// body, see [`Options::streaming_reserve`]. Unused space is kept as padding at the end of the
+/// reference data, so the file only matches [`write`](super::write) byte for byte when the
+/// reservation is exact.
+///
+/// Lists are cheapest when their length is given up front, as it is for `Vec`s, slices and
+/// tuples. Any other list is held in memory until it ends.
+pub fn write_streaming<W: Write + Seek, T: ?Sized + Serialize>(
+    writer: W,
+    value: &T,
+) -> Result<(), Error> {
+    write_streaming_with(writer, value, Options::default())
+}
+
+/// Serializes `value` straight into `writer` using `options`, see [`write_streaming`]
+pub fn write_streaming_with<W: Write + Seek, T: ?Sized + Serialize>(
+    writer: W,
+    value: &T,
+    options: Options,
+) -> Result<(), Error> {
+    let mut stream = Stream::new(writer, options)?;
+    required(&mut stream, options, value)?;
+    stream.finish()
+}
@@ -0,0 +1,330 @@
+//! A representation of [`Value`] which keeps the type of every param.
+//!
+//! `Value`'s own `Serialize` impl writes plain numbers, so a `u8` and an `i32` param look the
+//! same in JSON and can't be told apart when reading them back. [`Tagged`] and [`TaggedRef`]
+//! instead write every param as a single entry map from its type to its value, which works with
+//! any serde format:
+//!
+//! ```
+//! # use serde_prc::{tagged::{Tagged, TaggedRef}, Value};
+//! let value = Value::List(vec![Value::U8(3), Value::I32(-1)]);
+//! let json = serde_json::to_string(&TaggedRef(&value)).unwrap();
+//! assert_eq!(json, r#"{"list":[{"u8":3},{"i32":-1}]}"#);
+//!
+//! let Tagged(back) = serde_json::from_str(&json).unwrap();
+//! assert_eq!(back, value);
+//! ```
+//!
+//! The tags are `bool`, `i8`, `u8`, `i16`, `u16`, `i32`, `u32`, `f32`, `hash`, `string`, `list`
+//! and `map`. Hashes, including map keys, are written the same way as by `Value`: as a label or
+//! `0x0123456789` hex literal in human readable formats and as a `u64` otherwise.
+//!
+//! `Value` fields can use the representation with `#[serde(with = "serde_prc::tagged")]`.
+
+use std::fmt::Formatter;
+
+use hash40::Hash40;
+use indexmap::IndexMap;
+use serde::{
+    de::{EnumAccess, Error, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{ser::HashParam, ParamId, Value};
+
+const NAME: &str = "Value";
+
+/// The tag of each param type, in `ParamId` order
+const TAGS: &[&str] = &[
+    "bool", "i8", "u8", "i16", "u16", "i32", "u32", "f32", "hash", "string", "list", "map",
+];
+
+/// An owned [`Value`] which is serialized and deserialized with its param types
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tagged(pub Value);
+
+/// A borrowed [`Value`] which is serialized with its param types
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TaggedRef<'a>(pub &'a Value);
+
+impl From<Value> for Tagged {
+    fn from(value: Value) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Tagged> for Value {
+    fn from(value: Tagged) -> Self {
+        value.0
+    }
+}
+
+pub fn serialize<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+    TaggedRef(value).serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+    Tagged::deserialize(deserializer).map(Value::from)
+}
+
+impl Serialize for Tagged {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        TaggedRef(&self.0).serialize(serializer)
+    }
+}
+
+impl Serialize for TaggedRef<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let index = self.0.param_id() as u32 - 1;
+        let tag = TAGS[index as usize];
+
+        match self.0 {
+            Value::Bool(v) => serializer.serialize_newtype_variant(NAME, index, tag, v),
+            Value::I8(v) => serializer.serialize_newtype_variant(NAME, index, tag, v),
+            Value::U8(v) => serializer.serialize_newtype_variant(NAME, index, tag, v),
+            Value::I16(v) => serializer.serialize_newtype_variant(NAME, index, tag, v),
+            Value::U16(v) => serializer.serialize_newtype_variant(NAME, index, tag, v),
+            Value::I32(v) => serializer.serialize_newtype_variant(NAME, index, tag, v),
+            Value::U32(v) => serializer.serialize_newtype_variant(NAME, index, tag, v),
+            Value::F32(v) => serializer.serialize_newtype_variant(NAME, index, tag, v),
+            Value::Hash(v) => serializer.serialize_newtype_variant(NAME, index, tag, &Hash(*v)),
+            Value::String(v) => serializer.serialize_newtype_variant(NAME, index, tag, v),
+            Value::List(v) => serializer.serialize_newtype_variant(NAME, index, tag, &List(v)),
+            Value::Map(v) => serializer.serialize_newtype_variant(NAME, index, tag, &Map(v)),
+        }
+    }
+}
+
+/// A hash written the same way as `Value::Hash`
+struct Hash(Hash40);
+
+impl Serialize for Hash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&format!("{}", self.0))
+        } else {
+            HashParam(self.0).serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(HashVisitor)
+        } else {
+            deserializer.deserialize_u64(HashVisitor)
+        }
+    }
+}
+
+struct HashVisitor;
+
+impl<'de> Visitor<'de> for HashVisitor {
+    type Value = Hash;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a hash label, hex literal or u64")
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Hash(Hash40(v)))
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        crate::hash_from_label(v)
+            .map(Hash)
+            .ok_or_else(|| E::invalid_value(Unexpected::Str(v), &self))
+    }
+}
+
+struct List<'a>(&'a [Value]);
+
+impl Serialize for List<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter().map(TaggedRef))
+    }
+}
+
+struct Map<'a>(&'a IndexMap<Hash40, Value>);
+
+impl Serialize for Map<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(
+            self.0
+                .iter()
+                .map(|(key, value)| (Hash(*key), TaggedRef(value))),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for Tagged {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_enum(NAME, TAGS, TaggedVisitor)
+    }
+}
+
+/// The tag of a param
+struct Tag(ParamId);
+
+impl Tag {
+    fn from_index<E: Error>(index: usize) -> Result<Self, E> {
+        index
+            .checked_add(1)
+            .and_then(|id| u8::try_from(id).ok())
+            .and_then(|id| ParamId::try_from(id).ok())
+            .map(Tag)
+            .ok_or_else(|| E::invalid_value(Unexpected::Unsigned(index as u64), &TagVisitor))
+    }
+}
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(TagVisitor)
+    }
+}
+
+struct TagVisitor;
+
+impl<'de> Visitor<'de> for TagVisitor {
+    type Value = Tag;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a param type")
+    }
+
+    fn visit_u64<E: Error>(self, v: u64) -> Result<Self::Value, E> {
+        Tag::from_index(usize::try_from(v).unwrap_or(usize::MAX))
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        match TAGS.iter().position(|tag| *tag == v) {
+            Some(index) => Tag::from_index(index),
+            None => Err(E::unknown_variant(v, TAGS)),
+        }
+    }
+}
+
+struct TaggedVisitor;
+
+impl<'de> Visitor<'de> for TaggedVisitor {
+    type Value = Tagged;
+
+    fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+        formatter.write_str("a tagged prc value")
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        let (Tag(id), variant) = data.variant::<Tag>()?;
+
+        let value = match id {
+            ParamId::Bool => Value::Bool(variant.newtype_variant()?),
+            ParamId::I8 => Value::I8(variant.newtype_variant()?),
+            ParamId::U8 => Value::U8(variant.newtype_variant()?),
+            ParamId::I16 => Value::I16(variant.newtype_variant()?),
+            ParamId::U16 => Value::U16(variant.newtype_variant()?),
+            ParamId::I32 => Value::I32(variant.newtype_variant()?),
+            ParamId::U32 => Value::U32(variant.newtype_variant()?),
+            ParamId::F32 => Value::F32(variant.newtype_variant()?),
+            ParamId::Hash => Value::Hash(variant.newtype_variant::<Hash>()?.0),
+            ParamId::String => Value::String(variant.newtype_variant()?),
+            ParamId::List => Value::List(variant.newtype_variant::<TaggedList>()?.0),
+            ParamId::Map => Value::Map(variant.newtype_variant::<TaggedMap>()?.0),
+        };
+
+        Ok(Tagged(value))
+    }
+}
+
+struct TaggedList(Vec<Value>);
+
+impl<'de> Deserialize<'de> for TaggedList {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ListVisitor;
+
+        impl<'de> Visitor<'de> for ListVisitor {
+            type Value = TaggedList;
+
+            fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+                formatter.write_str("a list of tagged prc values")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut list = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+                while let Some(Tagged(value)) = seq.next_element()? {
+                    list.push(value);
+                }
+
+                Ok(TaggedList(list))
+            }
+        }
+
+        deserializer.deserialize_seq(ListVisitor)
+    }
+}
+
+struct TaggedMap(IndexMap<Hash40, Value>);
+
+impl<'de> Deserialize<'de> for TaggedMap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct MapVisitor;
+
+        impl<'de> Visitor<'de> for MapVisitor {
+            type Value = TaggedMap;
+
+            fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+                formatter.write_str("a map of hashes to tagged prc values")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut object = IndexMap::with_capacity(map.size_hint().unwrap_or_default());
+                while let Some((Hash(key), Tagged(value))) = map.next_entry()? {
+                    object.insert(key, value);
+                }
+
+                Ok(TaggedMap(object))
+            }
+        }
+
+        deserializer.deserialize_map(MapVisitor)
+    }
+}
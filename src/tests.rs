@@ -69,6 +69,11 @@ decl_value_tests! {
     }
 }
 
+/// The size of the reference data given in the header of a written file
+fn reference_size(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[0xC..0x10].try_into().unwrap())
+}
+
 mod hash {
     use super::*;
     const FIRST: &[u8] = &[0x09, 0x00, 0x00, 0x00, 0x00];
@@ -224,3 +229,2527 @@ fn deserialize_list() {
         [19i32, 128i32, -1i32]
     );
 }
+
+mod header {
+    use std::io::Cursor;
+
+    use crate::{de::ErrorKind, from_reader, from_slice, Value};
+    use serial_test::serial;
+
+    fn header(hash_size: u32, ref_size: u32) -> Vec<u8> {
+        let mut data = b"paracobn".to_vec();
+        data.extend_from_slice(&hash_size.to_le_bytes());
+        data.extend_from_slice(&ref_size.to_le_bytes());
+        data
+    }
+
+    #[test]
+    #[serial]
+    fn bad_magic() {
+        let mut data = header(0, 0);
+        data[0] = b'P';
+        let error = from_slice::<Value>(&data).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::BadMagic(magic) if magic == b"Paracobn"));
+    }
+
+    #[test]
+    #[serial]
+    fn truncated_header() {
+        let data = header(0, 0);
+        for len in [0, 4, 8, 12, 15] {
+            let error = from_slice::<Value>(&data[..len]).unwrap_err();
+            assert!(matches!(error.kind(), ErrorKind::TruncatedHeader));
+        }
+
+        // Hash table claims two entries but only holds one
+        let mut data = header(16, 0);
+        data.extend_from_slice(&[0u8; 8]);
+        let error = from_slice::<Value>(&data).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::TruncatedHeader));
+    }
+
+    #[test]
+    #[serial]
+    fn misaligned_hash_table() {
+        let mut data = header(12, 0);
+        data.extend_from_slice(&[0u8; 12]);
+        let error = from_slice::<Value>(&data).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::MisalignedHashTable(12)));
+    }
+
+    #[test]
+    #[serial]
+    fn reference_data_past_end() {
+        let mut data = header(8, 0xFFFF_FFFF);
+        data.extend_from_slice(&[0u8; 8]);
+        data.extend_from_slice(b"foo\0");
+        let error = from_slice::<Value>(&data).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::ReferenceDataPastEnd {
+                start: 0x18,
+                size: 0xFFFF_FFFF
+            }
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn slice_matches_reader() {
+        let mut bad_magic = header(0, 0);
+        bad_magic[0] = b'P';
+
+        let mut truncated_hashes = header(16, 0);
+        truncated_hashes.extend_from_slice(&[0u8; 8]);
+
+        let mut misaligned = header(12, 0);
+        misaligned.extend_from_slice(&[0u8; 12]);
+
+        let mut past_end = header(8, 0xFFFF_FFFF);
+        past_end.extend_from_slice(&[0u8; 8]);
+
+        for data in [
+            bad_magic,
+            header(0, 0)[..12].to_vec(),
+            truncated_hashes,
+            misaligned,
+            past_end,
+        ] {
+            let slice = from_slice::<Value>(&data).unwrap_err();
+            let reader = from_reader::<Value, _>(Cursor::new(&data)).unwrap_err();
+            assert_eq!(slice.to_string(), reader.to_string());
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn truncated_body() {
+        let data = header(0, 0);
+        let error = from_slice::<Value>(&data).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::IO(_)));
+    }
+}
+
+mod borrowed {
+    use crate::{from_reader, from_slice, to_vec, Value};
+    use hash40::hash40;
+    use indexmap::IndexMap;
+    use serde::Deserialize;
+    use serial_test::serial;
+
+    #[derive(Deserialize)]
+    struct Fighter<'a> {
+        name: &'a str,
+        series: &'a str,
+        weight: u8,
+    }
+
+    fn fighter() -> Vec<u8> {
+        let mut map = IndexMap::new();
+        map.insert(hash40("name"), Value::String("mario".to_string()));
+        map.insert(hash40("series"), Value::String("mario".to_string()));
+        map.insert(hash40("weight"), Value::U8(98));
+        to_vec(&Value::Map(map)).unwrap()
+    }
+
+    fn is_within(bytes: &[u8], string: &str) -> bool {
+        bytes.as_ptr_range().contains(&string.as_ptr())
+    }
+
+    #[test]
+    #[serial]
+    fn borrow_top_level_str() {
+        let bytes = to_vec(&Value::String("foo".to_string())).unwrap();
+        let string: &str = from_slice(&bytes).unwrap();
+        assert_eq!(string, "foo");
+        assert!(is_within(&bytes, string));
+    }
+
+    #[test]
+    #[serial]
+    fn borrow_struct_fields() {
+        let bytes = fighter();
+        let fighter: Fighter = from_slice(&bytes).unwrap();
+        assert_eq!(fighter.name, "mario");
+        assert_eq!(fighter.series, "mario");
+        assert_eq!(fighter.weight, 98);
+        assert!(is_within(&bytes, fighter.name));
+        assert!(is_within(&bytes, fighter.series));
+    }
+
+    #[test]
+    #[serial]
+    fn reader_and_slice_agree() {
+        let bytes = fighter();
+        let from_slice: Value = from_slice(&bytes).unwrap();
+        let from_reader: Value = from_reader(std::io::Cursor::new(&bytes)).unwrap();
+        assert_eq!(from_slice, from_reader);
+    }
+}
+
+mod file {
+    use crate::{de::ErrorKind, to_vec, ParamFile, ParamId, Value};
+    use hash40::hash40;
+    use indexmap::IndexMap;
+    use serial_test::serial;
+
+    fn fighter(kind: &str, walk_speed: f32) -> Value {
+        let mut map = IndexMap::new();
+        map.insert(hash40("fighter_kind"), Value::Hash(hash40(kind)));
+        map.insert(hash40("name"), Value::String(kind.to_string()));
+        map.insert(hash40("walk_speed_max"), Value::F32(walk_speed));
+        Value::Map(map)
+    }
+
+    fn fighter_param() -> Vec<u8> {
+        let mut root = IndexMap::new();
+        root.insert(
+            hash40("fighter_param_table"),
+            Value::List(vec![
+                fighter("fighter_kind_mario", 1.1),
+                fighter("fighter_kind_donkey", 1.2),
+                fighter("fighter_kind_link", 1.3),
+            ]),
+        );
+        root.insert(hash40("version"), Value::U32(13));
+        to_vec(&Value::Map(root)).unwrap()
+    }
+
+    #[test]
+    #[serial]
+    fn random_access() {
+        let bytes = fighter_param();
+        let file = ParamFile::from_slice(&bytes).unwrap();
+
+        let table = file.get(hash40("fighter_param_table")).unwrap();
+        assert_eq!(table.param_id(), ParamId::List);
+        assert_eq!(table.len().unwrap(), 3);
+
+        let donkey = table.index(1).unwrap();
+        assert_eq!(
+            donkey
+                .get(hash40("walk_speed_max"))
+                .unwrap()
+                .to_value()
+                .unwrap(),
+            Value::F32(1.2)
+        );
+        assert_eq!(
+            donkey
+                .get(hash40("name"))
+                .unwrap()
+                .deserialize::<&str>()
+                .unwrap(),
+            "fighter_kind_donkey"
+        );
+
+        assert_eq!(
+            file.get(hash40("version"))
+                .unwrap()
+                .deserialize::<u32>()
+                .unwrap(),
+            13
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn matches_full_decode() {
+        let bytes = fighter_param();
+        let file = ParamFile::from_vec(bytes.clone()).unwrap();
+        let value: Value = crate::from_slice(&bytes).unwrap();
+        assert_eq!(file.root().unwrap().to_value().unwrap(), value);
+
+        let entries = file.root().unwrap().entries().unwrap();
+        let keys: Vec<_> = entries.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, vec![hash40("fighter_param_table"), hash40("version")]);
+    }
+
+    #[test]
+    #[serial]
+    fn unvisited_params_are_not_decoded() {
+        let mut bytes = fighter_param();
+        let file = ParamFile::from_slice(&bytes).unwrap();
+        let mario = file
+            .get(hash40("fighter_param_table"))
+            .unwrap()
+            .index(0)
+            .unwrap()
+            .offset();
+        drop(file);
+
+        // Corrupt the first fighter's param id, the others should still be reachable
+        bytes[mario] = 0xFF;
+        let file = ParamFile::from_slice(&bytes).unwrap();
+        let table = file.get(hash40("fighter_param_table")).unwrap();
+        assert!(matches!(
+            table.index(0).unwrap_err().kind(),
+            ErrorKind::InvalidParamId(0xFF)
+        ));
+        assert_eq!(
+            table
+                .index(2)
+                .unwrap()
+                .get(hash40("walk_speed_max"))
+                .unwrap()
+                .to_value()
+                .unwrap(),
+            Value::F32(1.3)
+        );
+        assert!(crate::from_slice::<Value>(&bytes).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn lookup_errors() {
+        let bytes = fighter_param();
+        let file = ParamFile::from_slice(&bytes).unwrap();
+
+        assert!(matches!(
+            file.get(hash40("missing")).unwrap_err().kind(),
+            ErrorKind::MissingKey(key) if *key == hash40("missing")
+        ));
+
+        let table = file.get(hash40("fighter_param_table")).unwrap();
+        assert!(matches!(
+            table.index(3).unwrap_err().kind(),
+            ErrorKind::IndexOutOfBounds { index: 3, len: 3 }
+        ));
+        assert!(matches!(
+            table.get(hash40("name")).unwrap_err().kind(),
+            ErrorKind::UnexpectedParam {
+                expected: ParamId::Map,
+                found: ParamId::List
+            }
+        ));
+
+        let version = file.get(hash40("version")).unwrap();
+        assert!(matches!(
+            version
+                .reference_offset(ParamId::String)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::UnexpectedParam {
+                expected: ParamId::String,
+                found: ParamId::U32
+            }
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn get_only_decodes_the_match() {
+        let mut bytes = fighter_param();
+        let file = ParamFile::from_slice(&bytes).unwrap();
+        let version = file.get(hash40("version")).unwrap().offset();
+        drop(file);
+
+        // Corrupt a sibling of the looked up entry, which `entries` would have to decode
+        bytes[version] = 0xFF;
+        let file = ParamFile::from_slice(&bytes).unwrap();
+        assert!(file.root().unwrap().entries().is_err());
+        assert_eq!(
+            file.get(hash40("fighter_param_table"))
+                .unwrap()
+                .len()
+                .unwrap(),
+            3
+        );
+        assert!(matches!(
+            file.get(hash40("version")).unwrap_err().kind(),
+            ErrorKind::InvalidParamId(0xFF)
+        ));
+    }
+}
+
+mod document {
+    use crate::{from_slice, to_vec, Document, Value};
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use hash40::hash40;
+    use serial_test::serial;
+
+    /// A file laid out differently from how `to_vec` would write it: the hash table is not in
+    /// first-seen order, the struct tables come before the strings, the two inner maps have their
+    /// own copies of the same struct table and there is an unreferenced string
+    fn handmade() -> Vec<u8> {
+        let hashes = [hash40("y"), hash40("x"), hash40("b"), hash40("a")];
+
+        let mut reference = vec![];
+        for (hash, offset) in [(3u32, 9u32), (2, 28), (1, 9), (0, 14), (1, 9), (0, 14)] {
+            reference.write_u32::<LittleEndian>(hash).unwrap();
+            reference.write_u32::<LittleEndian>(offset).unwrap();
+        }
+        reference.extend_from_slice(b"unused\0foo\0bar\0");
+
+        let mut body = vec![];
+        body.extend_from_slice(&[0x0C, 2, 0, 0, 0, 0, 0, 0, 0]);
+        for (table, int, string) in [(16u32, 1i32, 55u32), (32, 2, 59)] {
+            body.extend_from_slice(&[0x0C, 2, 0, 0, 0]);
+            body.write_u32::<LittleEndian>(table).unwrap();
+            body.write_u8(0x06).unwrap();
+            body.write_i32::<LittleEndian>(int).unwrap();
+            body.write_u8(0x0A).unwrap();
+            body.write_u32::<LittleEndian>(string).unwrap();
+        }
+
+        let mut file = b"paracobn".to_vec();
+        file.write_u32::<LittleEndian>(hashes.len() as u32 * 8)
+            .unwrap();
+        file.write_u32::<LittleEndian>(reference.len() as u32)
+            .unwrap();
+        for hash in hashes {
+            file.write_u64::<LittleEndian>(hash.0).unwrap();
+        }
+        file.extend_from_slice(&reference);
+        file.extend_from_slice(&body);
+        file
+    }
+
+    #[test]
+    #[serial]
+    fn unmodified_round_trip() {
+        let bytes = handmade();
+        let value: Value = from_slice(&bytes).unwrap();
+
+        // The plain value loses the original layout...
+        assert_ne!(to_vec(&value).unwrap(), bytes);
+
+        // ...but the document keeps it
+        let document = Document::from_slice(&bytes).unwrap();
+        assert_eq!(document.root(), &value);
+        assert_eq!(document.to_vec().unwrap(), bytes);
+
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(
+            Document::from_slice(&bytes).unwrap().to_vec().unwrap(),
+            bytes
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn edits_stay_local() {
+        let bytes = handmade();
+        let mut document = Document::from_slice(&bytes).unwrap();
+
+        let Value::Map(root) = document.root_mut() else {
+            unreachable!()
+        };
+        let Value::Map(a) = &mut root[&hash40("a")] else {
+            unreachable!()
+        };
+        a[&hash40("x")] = Value::I32(100);
+
+        let edited = document.to_vec().unwrap();
+        assert_eq!(edited.len(), bytes.len());
+        let differences: Vec<_> = (0..bytes.len())
+            .filter(|index| bytes[*index] != edited[*index])
+            .collect();
+        assert_eq!(differences, vec![bytes.len() - 28]);
+
+        // A new string and a new key are appended after the original data
+        let Value::Map(root) = document.root_mut() else {
+            unreachable!()
+        };
+        let Value::Map(b) = &mut root[&hash40("b")] else {
+            unreachable!()
+        };
+        b[&hash40("y")] = Value::String("baz".to_string());
+        b.insert(hash40("z"), Value::Bool(true));
+
+        let edited = document.to_vec().unwrap();
+        assert_eq!(&edited[0x10..0x30], &bytes[0x10..0x30]);
+        assert_eq!(edited[0x38..0x38 + 63], bytes[0x30..0x30 + 63]);
+        assert_eq!(from_slice::<Value>(&edited).unwrap(), *document.root());
+    }
+}
+
+mod enums {
+    use crate::{from_slice, to_vec, Value};
+    use hash40::hash40;
+    use indexmap::IndexMap;
+    use serde::{Deserialize, Serialize};
+    use serial_test::serial;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Kind {
+        Mario,
+        Luigi,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Param {
+        Speed(f32),
+        Position(f32, f32),
+        Named { id: i32, kind: Kind },
+        Reset,
+    }
+
+    fn single(key: &str, value: Value) -> Value {
+        let mut map = IndexMap::new();
+        map.insert(hash40(key), value);
+        Value::Map(map)
+    }
+
+    fn parse<T: for<'de> Deserialize<'de>>(value: &Value) -> Result<T, crate::de::Error> {
+        from_slice(&to_vec(value).unwrap())
+    }
+
+    #[test]
+    #[serial]
+    fn unit_variant_from_string() {
+        assert_eq!(
+            parse::<Kind>(&Value::String("Luigi".to_string())).unwrap(),
+            Kind::Luigi
+        );
+        assert!(parse::<Kind>(&Value::String("Peach".to_string())).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn unit_variant_from_hash() {
+        assert_eq!(
+            parse::<Kind>(&Value::Hash(hash40("Mario"))).unwrap(),
+            Kind::Mario
+        );
+        assert!(parse::<Kind>(&Value::Hash(hash40("Peach"))).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn unit_variant_round_trip() {
+        let bytes = to_vec(&Kind::Luigi).unwrap();
+        assert_eq!(from_slice::<Kind>(&bytes).unwrap(), Kind::Luigi);
+    }
+
+    #[test]
+    #[serial]
+    fn data_variants_from_maps() {
+        assert_eq!(
+            parse::<Param>(&single("Speed", Value::F32(1.5))).unwrap(),
+            Param::Speed(1.5)
+        );
+        assert_eq!(
+            parse::<Param>(&single(
+                "Position",
+                Value::List(vec![Value::F32(1.0), Value::F32(-2.0)])
+            ))
+            .unwrap(),
+            Param::Position(1.0, -2.0)
+        );
+
+        let mut fields = IndexMap::new();
+        fields.insert(hash40("kind"), Value::Hash(hash40("Luigi")));
+        fields.insert(hash40("id"), Value::I32(7));
+        assert_eq!(
+            parse::<Param>(&single("Named", Value::Map(fields))).unwrap(),
+            Param::Named {
+                id: 7,
+                kind: Kind::Luigi
+            }
+        );
+
+        assert_eq!(
+            parse::<Param>(&Value::String("Reset".to_string())).unwrap(),
+            Param::Reset
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn invalid_enum_shapes() {
+        // Data carrying variants need a payload
+        assert!(parse::<Param>(&Value::String("Speed".to_string())).is_err());
+
+        // Maps must have exactly one entry
+        let mut map = IndexMap::new();
+        map.insert(hash40("Speed"), Value::F32(1.0));
+        map.insert(hash40("Reset"), Value::Bool(true));
+        assert!(parse::<Param>(&Value::Map(map)).is_err());
+
+        assert!(parse::<Kind>(&Value::I32(0)).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn data_variants_serialize_as_maps() {
+        let value: Value = from_slice(&to_vec(&Param::Speed(2.0)).unwrap()).unwrap();
+        assert_eq!(value, single("Speed", Value::F32(2.0)));
+
+        let value: Value = from_slice(&to_vec(&Param::Position(1.0, 2.0)).unwrap()).unwrap();
+        assert_eq!(
+            value,
+            single(
+                "Position",
+                Value::List(vec![Value::F32(1.0), Value::F32(2.0)])
+            )
+        );
+
+        let named = Param::Named {
+            id: 3,
+            kind: Kind::Mario,
+        };
+        let value: Value = from_slice(&to_vec(&named).unwrap()).unwrap();
+        let mut fields = IndexMap::new();
+        fields.insert(hash40("id"), Value::I32(3));
+        fields.insert(hash40("kind"), Value::String("Mario".to_string()));
+        assert_eq!(value, single("Named", Value::Map(fields)));
+    }
+
+    #[test]
+    #[serial]
+    fn data_variants_round_trip() {
+        let params = vec![
+            Param::Speed(2.0),
+            Param::Position(1.0, 2.0),
+            Param::Named {
+                id: 3,
+                kind: Kind::Mario,
+            },
+            Param::Reset,
+        ];
+
+        let bytes = to_vec(&params).unwrap();
+        assert_eq!(from_slice::<Vec<Param>>(&bytes).unwrap(), params);
+    }
+}
+
+mod options {
+    use crate::{from_slice, ser, to_vec, Value};
+    use hash40::hash40;
+    use serde::{Deserialize, Serialize};
+    use serial_test::serial;
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Entry {
+        id: u8,
+        added_in: Option<u32>,
+        name: Option<String>,
+    }
+
+    #[test]
+    #[serial]
+    fn none_fields_are_omitted() {
+        let entry = Entry {
+            id: 1,
+            added_in: Some(13),
+            name: None,
+        };
+
+        let value: Value = from_slice(&to_vec(&entry).unwrap()).unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&hash40("added_in")], Value::U32(13));
+        assert!(!map.contains_key(&hash40("name")));
+    }
+
+    #[test]
+    #[serial]
+    fn options_round_trip() {
+        let entries = vec![
+            Entry {
+                id: 1,
+                added_in: Some(13),
+                name: None,
+            },
+            Entry {
+                id: 2,
+                added_in: None,
+                name: Some("luigi".to_string()),
+            },
+        ];
+
+        let bytes = to_vec(&entries).unwrap();
+        assert_eq!(from_slice::<Vec<Entry>>(&bytes).unwrap(), entries);
+    }
+
+    #[test]
+    #[serial]
+    fn none_map_values_are_omitted() {
+        let mut map = HashMap::new();
+        map.insert("present", Some(1i32));
+        map.insert("absent", None);
+
+        let value: Value = from_slice(&to_vec(&map).unwrap()).unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&hash40("present")], Value::I32(1));
+    }
+
+    #[test]
+    #[serial]
+    fn none_outside_of_fields() {
+        assert!(matches!(
+            to_vec(&Option::<i32>::None).unwrap_err(),
+            ser::Error::UnsupportedValueType("none")
+        ));
+
+        #[derive(Serialize)]
+        struct Wrapper {
+            list: Vec<Option<i32>>,
+        }
+
+        let wrapper = Wrapper {
+            list: vec![Some(1), None],
+        };
+        assert!(matches!(
+            to_vec(&wrapper).unwrap_err(),
+            ser::Error::UnsupportedValueType("none")
+        ));
+    }
+}
+
+mod newtypes {
+    use crate::{from_slice, to_vec, Value};
+    use hash40::{hash40, Hash40};
+    use serde::{Deserialize, Serialize};
+    use serial_test::serial;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct FighterKind(u64);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Key(String);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Frames(u16);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Attack {
+        kind: FighterKind,
+        startup: Frames,
+    }
+
+    #[test]
+    #[serial]
+    fn newtype_values_are_transparent() {
+        let attack = Attack {
+            kind: FighterKind(hash40("fighter_kind_mario").0),
+            startup: Frames(3),
+        };
+
+        let bytes = to_vec(&attack).unwrap();
+        let value: Value = from_slice(&bytes).unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(
+            map[&hash40("kind")],
+            Value::Hash(hash40("fighter_kind_mario"))
+        );
+        assert_eq!(map[&hash40("startup")], Value::U16(3));
+
+        assert_eq!(from_slice::<Attack>(&bytes).unwrap(), attack);
+    }
+
+    #[test]
+    #[serial]
+    fn newtype_keys_are_transparent() {
+        Hash40::label_map().lock().unwrap().clear();
+
+        let mut by_kind = BTreeMap::new();
+        by_kind.insert(FighterKind(hash40("mario").0), Frames(1));
+        by_kind.insert(FighterKind(hash40("luigi").0), Frames(2));
+
+        let bytes = to_vec(&by_kind).unwrap();
+        let value: Value = from_slice(&bytes).unwrap();
+        assert_eq!(value.as_map().unwrap()[&hash40("luigi")], Value::U16(2));
+        assert_eq!(
+            from_slice::<BTreeMap<FighterKind, Frames>>(&bytes).unwrap(),
+            by_kind
+        );
+
+        let mut by_name = BTreeMap::new();
+        by_name.insert(Key("mario".to_string()), Frames(1));
+
+        let bytes = to_vec(&by_name).unwrap();
+        let value: Value = from_slice(&bytes).unwrap();
+        assert_eq!(value.as_map().unwrap()[&hash40("mario")], Value::U16(1));
+    }
+}
+
+mod hash_keys {
+    use crate::{from_slice, ser, to_vec, Value};
+    use hash40::{hash40, Hash40};
+    use indexmap::IndexMap;
+    use serial_test::serial;
+    use std::collections::HashMap;
+
+    fn file(keys: &[Hash40]) -> Vec<u8> {
+        let map: IndexMap<_, _> = keys
+            .iter()
+            .enumerate()
+            .map(|(index, key)| (*key, Value::I32(index as i32)))
+            .collect();
+        to_vec(&Value::Map(map)).unwrap()
+    }
+
+    #[test]
+    #[serial]
+    fn unlabeled_keys_round_trip() {
+        Hash40::label_map().lock().unwrap().clear();
+
+        let bytes = file(&[hash40("foo"), hash40("bar")]);
+        let map: HashMap<String, i32> = from_slice(&bytes).unwrap();
+        assert_eq!(map["0x038c736521"], 0);
+
+        let value: Value = from_slice(&to_vec(&map).unwrap()).unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map[&hash40("foo")], Value::I32(0));
+        assert_eq!(map[&hash40("bar")], Value::I32(1));
+    }
+
+    #[test]
+    #[serial]
+    fn labeled_keys_round_trip() {
+        Hash40::label_map().lock().unwrap().clear();
+
+        // A custom label which doesn't hash to the value it labels
+        let unknown = Hash40(0x0A_1234_5678);
+        Hash40::label_map()
+            .lock()
+            .unwrap()
+            .add_custom_labels([(unknown, "my_label".to_string())].into_iter());
+        Hash40::label_map()
+            .lock()
+            .unwrap()
+            .add_labels(["foo".to_string()]);
+
+        let bytes = file(&[hash40("foo"), unknown]);
+        let map: HashMap<String, i32> = from_slice(&bytes).unwrap();
+        assert_eq!(map["foo"], 0);
+        assert_eq!(map["my_label"], 1);
+
+        let value: Value = from_slice(&to_vec(&map).unwrap()).unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map[&hash40("foo")], Value::I32(0));
+        assert_eq!(map[&unknown], Value::I32(1));
+
+        Hash40::label_map().lock().unwrap().clear();
+    }
+
+    #[test]
+    #[serial]
+    fn strict_label_map() {
+        Hash40::label_map().lock().unwrap().clear();
+        Hash40::label_map().lock().unwrap().strict = true;
+
+        let mut map = HashMap::new();
+        map.insert("not_a_label", 0);
+        let result = to_vec(&map);
+
+        Hash40::label_map().lock().unwrap().strict = false;
+
+        assert!(matches!(
+            result.unwrap_err(),
+            ser::Error::UnknownLabel(label) if label == "not_a_label"
+        ));
+    }
+}
+
+mod hash_str {
+    use crate::{from_slice, to_vec, Value};
+    use hash40::{hash40, Hash40};
+    use serde::{Deserialize, Serialize};
+    use serial_test::serial;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Chara {
+        #[serde(with = "crate::hash_str")]
+        chara_id: String,
+
+        #[serde(default, with = "crate::hash_str::option")]
+        original: Option<String>,
+
+        #[serde(with = "crate::hash_str::vec")]
+        colors: Vec<String>,
+    }
+
+    #[test]
+    #[serial]
+    fn stored_as_hashes() {
+        Hash40::label_map().lock().unwrap().clear();
+        Hash40::label_map()
+            .lock()
+            .unwrap()
+            .add_labels(["mario".to_string(), "c00".to_string()]);
+
+        let chara = Chara {
+            chara_id: "mario".to_string(),
+            original: None,
+            colors: vec!["c00".to_string(), "0x0123456789".to_string()],
+        };
+
+        let bytes = to_vec(&chara).unwrap();
+        let value: Value = from_slice(&bytes).unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map[&hash40("chara_id")], Value::Hash(hash40("mario")));
+        assert!(!map.contains_key(&hash40("original")));
+        assert_eq!(
+            map[&hash40("colors")],
+            Value::List(vec![
+                Value::Hash(hash40("c00")),
+                Value::Hash(Hash40(0x0123456789))
+            ])
+        );
+
+        assert_eq!(from_slice::<Chara>(&bytes).unwrap(), chara);
+
+        Hash40::label_map().lock().unwrap().clear();
+    }
+
+    #[test]
+    #[serial]
+    fn unlabeled_hashes() {
+        Hash40::label_map().lock().unwrap().clear();
+
+        let chara = Chara {
+            chara_id: "mario".to_string(),
+            original: Some("luigi".to_string()),
+            colors: vec![],
+        };
+
+        let read: Chara = from_slice(&to_vec(&chara).unwrap()).unwrap();
+        assert_eq!(read.chara_id, format!("0x{:010x}", hash40("mario").0));
+        assert_eq!(read.original, Some(format!("0x{:010x}", hash40("luigi").0)));
+
+        // Writing the hex form back produces the same hashes
+        let value: Value = from_slice(&to_vec(&read).unwrap()).unwrap();
+        assert_eq!(
+            value.as_map().unwrap()[&hash40("original")],
+            Value::Hash(hash40("luigi"))
+        );
+    }
+}
+
+mod ser_options {
+    use crate::{
+        from_slice,
+        ser::{self, HashOrder, I64Mapping, Options, U64Mapping},
+        to_vec, Value,
+    };
+    use hash40::{hash40, Hash40};
+    use serde::Serialize;
+    use serial_test::serial;
+
+    use super::reference_size;
+
+    #[derive(Serialize)]
+    struct Pair {
+        a: i32,
+        b: i32,
+    }
+
+    #[test]
+    #[serial]
+    fn defaults_match_to_vec() {
+        let value = (5u64, -3i64, 1.5f64, "text");
+        assert_eq!(
+            Options::new().to_vec(&value).unwrap(),
+            to_vec(&value).unwrap()
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn integer_mapping() {
+        let bytes = Options::new()
+            .u64_mapping(U64Mapping::U32)
+            .to_vec(&5u64)
+            .unwrap();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), Value::U32(5));
+        assert!(Options::new()
+            .u64_mapping(U64Mapping::U32)
+            .to_vec(&u64::MAX)
+            .is_err());
+
+        assert!(matches!(
+            Options::new()
+                .i64_mapping(I64Mapping::Reject)
+                .to_vec(&5i64)
+                .unwrap_err(),
+            ser::Error::UnsupportedValueType("i64")
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn hashes_ignore_u64_mapping() {
+        #[derive(Serialize)]
+        struct Chara {
+            #[serde(with = "crate::hash_str")]
+            kind: String,
+            count: u64,
+        }
+
+        let kind = hash40("fighter_kind_mario");
+        let value = Value::List(vec![Value::Hash(kind), Value::U8(1)]);
+        let options = Options::new().u64_mapping(U64Mapping::U32);
+
+        let bytes = options.to_vec(&value).unwrap();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+
+        // Reserve exactly the one hash, so that the files are the same
+        let mut streamed = std::io::Cursor::new(Vec::new());
+        options
+            .streaming_reserve(8)
+            .write_streaming(&mut streamed, &value)
+            .unwrap();
+        assert_eq!(streamed.into_inner(), bytes);
+
+        let chara = Chara {
+            kind: "0x0123456789".to_string(),
+            count: 3,
+        };
+        let value: Value = from_slice(&options.to_vec(&chara).unwrap()).unwrap();
+        let map = value.as_map().unwrap();
+        assert_eq!(map[&hash40("kind")], Value::Hash(Hash40(0x0123456789)));
+        assert_eq!(map[&hash40("count")], Value::U32(3));
+    }
+
+    #[test]
+    #[serial]
+    fn lossy_floats() {
+        let strict = Options::new().lossy_floats(false);
+        assert!(matches!(
+            strict.to_vec(&0.1f64).unwrap_err(),
+            ser::Error::LossyFloat(v) if v == 0.1
+        ));
+
+        let bytes = strict.to_vec(&1.5f64).unwrap();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), Value::F32(1.5));
+    }
+
+    #[test]
+    #[serial]
+    fn dedup_strings() {
+        let value = vec!["same", "same", "same"];
+        let shared = to_vec(&value).unwrap();
+        let copied = Options::new().dedup_strings(false).to_vec(&value).unwrap();
+
+        assert_eq!(reference_size(&shared), 5);
+        assert_eq!(reference_size(&copied), 15);
+        assert_eq!(
+            from_slice::<Vec<String>>(&copied).unwrap(),
+            vec!["same", "same", "same"]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn share_structs() {
+        let value = vec![Pair { a: 1, b: 2 }, Pair { a: 3, b: 4 }];
+        let shared = to_vec(&value).unwrap();
+        let separate = Options::new().share_structs(false).to_vec(&value).unwrap();
+
+        assert_eq!(reference_size(&shared), 16);
+        assert_eq!(reference_size(&separate), 32);
+        assert_eq!(
+            from_slice::<Value>(&separate).unwrap(),
+            from_slice::<Value>(&shared).unwrap()
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn sorted_hashes() {
+        let value = Value::List(vec![
+            Value::Hash(Hash40(3)),
+            Value::Hash(Hash40(1)),
+            Value::Hash(Hash40(2)),
+        ]);
+        let bytes = Options::new()
+            .hash_order(HashOrder::Sorted)
+            .to_vec(&value)
+            .unwrap();
+
+        let hashes = bytes[0x10..0x28]
+            .chunks(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(hashes, [1, 2, 3]);
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+    }
+}
+
+mod strict {
+    use crate::{
+        de::{ErrorKind, Options},
+        from_slice, from_slice_with, to_vec, ParamId,
+    };
+    use hash40::Hash40;
+    use serde::{Deserialize, Serialize};
+    use serial_test::serial;
+
+    #[derive(Serialize)]
+    struct Written {
+        level: u8,
+        offset: i16,
+        scale: f32,
+        id: Hash40,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Widened {
+        level: i32,
+        offset: i32,
+        scale: f64,
+        id: String,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Exact {
+        level: u8,
+        offset: i16,
+        scale: f64,
+        id: String,
+    }
+
+    fn written() -> Vec<u8> {
+        to_vec(&Written {
+            level: 3,
+            offset: -2,
+            scale: 0.5,
+            id: Hash40(0x0123456789),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    #[serial]
+    fn lenient_by_default() {
+        assert_eq!(
+            from_slice::<Widened>(&written()).unwrap(),
+            Widened {
+                level: 3,
+                offset: -2,
+                scale: 0.5,
+                id: "0x0123456789".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn mismatched_width() {
+        let error =
+            from_slice_with::<Widened>(&written(), Options::new().strict(true)).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::UnexpectedParam {
+                expected: ParamId::I32,
+                found: ParamId::U8
+            }
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn mismatched_signedness() {
+        let bytes = to_vec(&vec![1u16]).unwrap();
+        let error = from_slice_with::<Vec<i16>>(&bytes, Options::new().strict(true)).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::UnexpectedParam {
+                expected: ParamId::I16,
+                found: ParamId::U16
+            }
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn exact_types() {
+        assert_eq!(
+            from_slice_with::<Exact>(&written(), Options::new().strict(true)).unwrap(),
+            Exact {
+                level: 3,
+                offset: -2,
+                scale: 0.5,
+                id: "0x0123456789".to_string(),
+            }
+        );
+
+        let bytes = to_vec(&(5i64, 7u64)).unwrap();
+        assert_eq!(
+            from_slice_with::<(i64, u64)>(&bytes, Options::new().strict(true)).unwrap(),
+            (5, 7)
+        );
+    }
+}
+
+mod struct_interning {
+    use std::collections::BTreeMap;
+
+    use crate::{from_slice, ser::FORCE_STRUCT_KEY_COLLISIONS, to_vec, Value};
+    use hash40::hash40;
+    use serde::Serialize;
+    use serial_test::serial;
+
+    use super::reference_size;
+
+    #[derive(Serialize)]
+    struct Pair {
+        a: i32,
+        b: i32,
+    }
+
+    #[derive(Serialize)]
+    struct Other {
+        c: u8,
+        d: bool,
+    }
+
+    #[derive(Serialize)]
+    struct Mixed {
+        pairs: Vec<Pair>,
+        other: Other,
+    }
+
+    fn with_collisions<T>(f: impl FnOnce() -> T) -> T {
+        FORCE_STRUCT_KEY_COLLISIONS.with(|force| force.set(true));
+        let result = f();
+        FORCE_STRUCT_KEY_COLLISIONS.with(|force| force.set(false));
+        result
+    }
+
+    #[test]
+    #[serial]
+    fn colliding_layouts_are_kept_apart() {
+        let value = Mixed {
+            pairs: vec![Pair { a: 1, b: 2 }, Pair { a: 3, b: 4 }],
+            other: Other { c: 5, d: true },
+        };
+
+        let bytes = with_collisions(|| to_vec(&value).unwrap());
+        assert_eq!(bytes, to_vec(&value).unwrap());
+
+        let read: Value = from_slice(&bytes).unwrap();
+        let map = read.as_map().unwrap();
+        let other = map[&hash40("other")].as_map().unwrap();
+        assert_eq!(other[&hash40("c")], Value::U8(5));
+        assert_eq!(other[&hash40("d")], Value::Bool(true));
+
+        let pairs = map[&hash40("pairs")].as_list().unwrap();
+        assert_eq!(pairs[1].as_map().unwrap()[&hash40("b")], Value::I32(4));
+    }
+
+    #[test]
+    #[serial]
+    fn empty_table_at_same_offset() {
+        // The empty map's struct table takes no space, so it starts where the next one does
+        let value = vec![
+            BTreeMap::new(),
+            BTreeMap::from([("0x0000000001".to_string(), 1)]),
+        ];
+
+        let read: Vec<BTreeMap<String, i32>> = from_slice(&to_vec(&value).unwrap()).unwrap();
+        assert_eq!(read, value);
+    }
+
+    #[test]
+    #[serial]
+    fn tables_sharing_an_offset() {
+        let value = Value::List(vec![
+            Value::Map([(hash40("a"), Value::I32(1)), (hash40("b"), Value::I32(2))].into()),
+            Value::Map([(hash40("a"), Value::I32(1))].into()),
+        ]);
+        let mut bytes = to_vec(&value).unwrap();
+
+        // Point the last map at the start of the first map's table, which begins with the same
+        // entry
+        let len = bytes.len();
+        bytes[len - 9..len - 5].copy_from_slice(&0u32.to_le_bytes());
+
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+    }
+
+    #[test]
+    #[serial]
+    fn identical_layouts_are_shared() {
+        let value = vec![Pair { a: 1, b: 2 }, Pair { a: 3, b: 4 }];
+        let bytes = with_collisions(|| to_vec(&value).unwrap());
+        assert_eq!(reference_size(&bytes), 16);
+    }
+
+    #[test]
+    #[serial]
+    fn last_value_size_is_not_part_of_the_layout() {
+        // Both tables only hold the offset of `a`, which doesn't depend on the size of its value
+        let value = Value::List(vec![
+            Value::Map([(hash40("a"), Value::U8(1))].into()),
+            Value::Map([(hash40("a"), Value::I32(1))].into()),
+        ]);
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(reference_size(&bytes), 8);
+    }
+}
+
+mod streaming {
+    use std::{cell::Cell, collections::BTreeMap, fs::File, io::Cursor};
+
+    use crate::{
+        from_slice,
+        ser::{self, write_streaming, HashOrder, Options},
+        to_vec, Value,
+    };
+    use serde::{ser::SerializeSeq, Serialize, Serializer};
+    use serial_test::serial;
+
+    use super::reference_size;
+
+    #[derive(Serialize)]
+    enum Shape {
+        Point,
+        Circle(f32),
+        Line(i32, i32),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[derive(Serialize)]
+    struct Entry {
+        name: String,
+        id: u64,
+        parent: Option<u64>,
+        shapes: Vec<Shape>,
+        tags: BTreeMap<String, Vec<i16>>,
+    }
+
+    fn entries() -> Vec<Entry> {
+        (0..8)
+            .map(|i| Entry {
+                name: format!("entry_{}", i % 3),
+                id: i,
+                parent: (i % 2 == 0).then_some(i / 2),
+                shapes: vec![
+                    Shape::Point,
+                    Shape::Circle(i as f32),
+                    Shape::Line(1, -1),
+                    Shape::Rect { w: 2, h: i as u8 },
+                ],
+                tags: (0..i)
+                    .map(|j| (format!("0x{j:010x}"), vec![j as i16; j as usize]))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn streamed<T: Serialize>(value: &T, options: Options) -> Vec<u8> {
+        let mut cursor = Cursor::new(Vec::new());
+        options.write_streaming(&mut cursor, value).unwrap();
+        cursor.into_inner()
+    }
+
+    /// The space taken up by the hash table and reference data of a written file, which is
+    /// what streaming has to reserve to write the same file
+    fn front(bytes: &[u8]) -> u32 {
+        u32::from_le_bytes(bytes[0x8..0xC].try_into().unwrap()) + reference_size(bytes)
+    }
+
+    /// Streams `value`, reserving exactly as much space as `write` ends up needing
+    fn exactly<T: Serialize>(value: &T, options: Options) -> Vec<u8> {
+        let front = front(&options.to_vec(value).unwrap());
+        streamed(value, options.streaming_reserve(front))
+    }
+
+    #[test]
+    #[serial]
+    fn matches_to_vec() {
+        let value = entries();
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(exactly(&value, Options::new()), bytes);
+
+        // Space which isn't needed is padding at the end of the reference data
+        let padded = streamed(&value, Options::new());
+        assert_eq!(
+            reference_size(&padded),
+            0x10000 - (front(&bytes) - reference_size(&bytes))
+        );
+        assert_eq!(
+            from_slice::<Value>(&padded).unwrap(),
+            from_slice::<Value>(&bytes).unwrap()
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn matches_to_vec_with_options() {
+        let value = entries();
+        for options in [
+            Options::new().dedup_strings(false),
+            Options::new().share_structs(false),
+            Options::new().hash_order(HashOrder::Sorted),
+        ] {
+            assert_eq!(
+                exactly(&value, options),
+                options.to_vec(&value).unwrap(),
+                "{options:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn writes_after_existing_data() {
+        let bytes = to_vec(&entries()).unwrap();
+        let mut cursor = Cursor::new(b"junk".to_vec());
+        cursor.set_position(4);
+        Options::new()
+            .streaming_reserve(front(&bytes))
+            .write_streaming(&mut cursor, &entries())
+            .unwrap();
+        assert_eq!(&cursor.get_ref()[4..], bytes);
+    }
+
+    #[test]
+    #[serial]
+    fn write_only_file() {
+        let path = std::env::temp_dir().join("serde_prc_write_only_file.prc");
+        let file = File::create(&path).unwrap();
+        write_streaming(file, &entries()).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            from_slice::<Value>(&bytes).unwrap(),
+            from_slice::<Value>(&to_vec(&entries()).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn reserve_too_small() {
+        let needed = front(&to_vec(&entries()).unwrap()) as usize;
+        let error = Options::new()
+            .streaming_reserve(8)
+            .write_streaming(Cursor::new(Vec::new()), &entries())
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ser::Error::ReserveTooSmall { needed: n, reserved: 8 } if n == needed
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn none_elements() {
+        let error = ser::write_streaming(Cursor::new(Vec::new()), &vec![Some(1), None]);
+        assert!(matches!(
+            error.unwrap_err(),
+            ser::Error::UnsupportedValueType("none")
+        ));
+    }
+
+    /// Serializes as a list with one more element every time
+    struct Growing(Cell<usize>);
+
+    impl Serialize for Growing {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let len = self.0.get();
+            self.0.set(len + 1);
+            serializer.collect_seq(0..len as i32)
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn serializes_once() {
+        // A list of numbers has no hash table or reference data, so nothing has to be reserved
+        let value = Growing(Cell::new(1));
+        let bytes = streamed(&value, Options::new().streaming_reserve(0));
+        assert_eq!(bytes, to_vec(&[0]).unwrap());
+        assert_eq!(value.0.get(), 2);
+    }
+
+    /// Serializes as a list which gives `hint` as its length up front
+    struct Hinted<T>(Vec<T>, Option<usize>);
+
+    impl<T: Serialize> Serialize for Hinted<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(self.1)?;
+            for element in &self.0 {
+                seq.serialize_element(element)?;
+            }
+            seq.end()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn unknown_lengths() {
+        let value = entries()
+            .into_iter()
+            .map(|entry| Hinted(vec![Hinted(vec![entry], None)], None))
+            .collect::<Vec<_>>();
+        let value = Hinted(value, None);
+
+        let expected = entries()
+            .into_iter()
+            .map(|entry| vec![vec![entry]])
+            .collect::<Vec<_>>();
+
+        for options in [Options::new(), Options::new().hash_order(HashOrder::Sorted)] {
+            let bytes = options.to_vec(&expected).unwrap();
+            assert_eq!(
+                streamed(&value, options.streaming_reserve(front(&bytes))),
+                bytes,
+                "{options:?}"
+            );
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn wrong_length_hints() {
+        // Spare offsets are left as padding
+        let bytes = streamed(&Hinted(vec![1, 2], Some(3)), Options::new());
+        assert_eq!(
+            from_slice::<Value>(&bytes).unwrap(),
+            Value::List(vec![Value::I32(1), Value::I32(2)])
+        );
+
+        let error = Options::new()
+            .write_streaming(Cursor::new(Vec::new()), &Hinted(vec![1, 2], Some(1)))
+            .unwrap_err();
+        assert!(matches!(error, ser::Error::ListTooLong { hint: 1 }));
+    }
+}
+
+mod slice_read {
+    use crate::{de::ErrorKind, from_reader, from_slice, to_vec, Value};
+    use hash40::Hash40;
+    use serde::Serialize;
+    use serial_test::serial;
+    use std::{collections::BTreeMap, io::Cursor};
+
+    #[derive(Serialize)]
+    struct Entry {
+        name: String,
+        values: Vec<i32>,
+        child: BTreeMap<String, f32>,
+    }
+
+    fn file() -> Vec<u8> {
+        let entries: Vec<Entry> = (0..8)
+            .map(|i| Entry {
+                name: format!("entry_{i}"),
+                values: (0..i).collect(),
+                child: [("scale".to_string(), i as f32)].into_iter().collect(),
+            })
+            .collect();
+
+        let mut root = BTreeMap::new();
+        root.insert("entries", entries);
+        to_vec(&root).unwrap()
+    }
+
+    #[test]
+    #[serial]
+    fn matches_reader() {
+        let bytes = file();
+        let from_slice: Value = from_slice(&bytes).unwrap();
+        let from_reader: Value = from_reader(Cursor::new(&bytes)).unwrap();
+        assert_eq!(from_slice, from_reader);
+        assert_eq!(
+            from_slice.as_map().unwrap()[&Hash40::new("entries")]
+                .as_list()
+                .unwrap()
+                .len(),
+            8
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn truncated_body() {
+        let bytes = file();
+        let bytes = &bytes[..bytes.len() - 3];
+
+        let slice_error = from_slice::<Value>(bytes).unwrap_err();
+        let reader_error = from_reader::<Value, _>(Cursor::new(bytes)).unwrap_err();
+        assert!(matches!(slice_error.kind(), ErrorKind::IO(_)));
+
+        // Positions are only looked up once the read has failed, but both paths still report
+        // where they were for every level of the stack
+        let frames = |error: &crate::de::Error| {
+            error
+                .to_string()
+                .lines()
+                .skip(1)
+                .map(|line| line.split(" @ ").next().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert!(!frames(&slice_error).is_empty());
+        assert_eq!(frames(&slice_error), frames(&reader_error));
+        assert!(!slice_error.to_string().contains("<unknown>"));
+    }
+}
+
+mod field_tables {
+    use crate::{from_slice, to_vec};
+    use serde::{Deserialize, Serialize};
+    use serial_test::serial;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Fighter {
+        name: String,
+        slot: u8,
+        weight: f32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Stage {
+        name: String,
+        slot: u8,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Db {
+        fighters: Vec<Fighter>,
+        stages: Vec<Stage>,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Names {
+        name: String,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct NamesDb {
+        fighters: Vec<Names>,
+        stages: Vec<Names>,
+    }
+
+    fn db() -> Db {
+        Db {
+            fighters: (0..32)
+                .map(|i| Fighter {
+                    name: format!("fighter_{i}"),
+                    slot: i as u8,
+                    weight: 90.0 + i as f32,
+                })
+                .collect(),
+            stages: (0..8)
+                .map(|i| Stage {
+                    name: format!("stage_{i}"),
+                    slot: i as u8,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn struct_lists() {
+        let bytes = to_vec(&db()).unwrap();
+        assert_eq!(from_slice::<Db>(&bytes).unwrap(), db());
+    }
+
+    #[test]
+    #[serial]
+    fn partial_structs() {
+        let bytes = to_vec(&db()).unwrap();
+        let names = from_slice::<NamesDb>(&bytes).unwrap();
+        assert_eq!(names.fighters[31].name, "fighter_31");
+        assert_eq!(names.stages[7].name, "stage_7");
+    }
+}
+
+mod skipping {
+    use crate::{de::ErrorKind, from_reader, from_slice, to_vec, Value};
+    use serde::{de::IgnoredAny, Deserialize, Serialize};
+    use serial_test::serial;
+    use std::{collections::BTreeMap, io::Cursor};
+
+    #[derive(Serialize)]
+    struct Full {
+        before: i32,
+        nested: Vec<BTreeMap<String, Vec<Vec<u8>>>>,
+        empty_list: Vec<u8>,
+        empty_map: BTreeMap<String, u8>,
+        after: String,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Partial {
+        before: i32,
+        after: String,
+    }
+
+    fn full() -> Full {
+        Full {
+            before: -7,
+            nested: (0..4)
+                .map(|i| {
+                    [
+                        ("a".to_string(), vec![vec![i; 3]; 2]),
+                        ("b".to_string(), vec![]),
+                    ]
+                    .into_iter()
+                    .collect()
+                })
+                .collect(),
+            empty_list: vec![],
+            empty_map: BTreeMap::new(),
+            after: "kept".to_string(),
+        }
+    }
+
+    /// Where the reader ends up after deserializing a `T` from the front of `bytes`
+    fn end_of<T: for<'de> Deserialize<'de>>(bytes: &[u8]) -> u64 {
+        let mut cursor = Cursor::new(bytes);
+        from_reader::<T, _>(&mut cursor).unwrap();
+        cursor.position()
+    }
+
+    #[test]
+    #[serial]
+    fn ignored_fields() {
+        let bytes = to_vec(&full()).unwrap();
+        assert_eq!(
+            from_slice::<Partial>(&bytes).unwrap(),
+            Partial {
+                before: -7,
+                after: "kept".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn skips_to_the_end() {
+        let bytes = to_vec(&full()).unwrap();
+        assert_eq!(end_of::<IgnoredAny>(&bytes), bytes.len() as u64);
+        assert_eq!(end_of::<Partial>(&bytes), bytes.len() as u64);
+        assert_eq!(end_of::<Value>(&bytes), bytes.len() as u64);
+
+        let bytes = to_vec(&vec![vec![1u8, 2], vec![], vec![3]]).unwrap();
+        assert_eq!(end_of::<IgnoredAny>(&bytes), bytes.len() as u64);
+        assert_eq!(end_of::<(Vec<u8>,)>(&bytes), bytes.len() as u64);
+    }
+
+    #[test]
+    #[serial]
+    fn truncated_leaf() {
+        let bytes = to_vec(&vec![1i32, 2, 3]).unwrap();
+        assert!(from_slice::<IgnoredAny>(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn offsets_must_move_forward() {
+        #[derive(Deserialize, Debug)]
+        struct Empty {}
+
+        let mut map = BTreeMap::new();
+        map.insert("x".to_string(), vec![1u8]);
+        let mut bytes = to_vec(&map).unwrap();
+
+        // The list is the last thing in the file: id, count, one offset and then its `u8` element.
+        // Point the element back at the list itself.
+        let offset = bytes.len() - 6;
+        bytes[offset..offset + 4].copy_from_slice(&0u32.to_le_bytes());
+
+        for error in [
+            from_slice::<Empty>(&bytes).unwrap_err(),
+            from_reader::<Empty, _>(Cursor::new(&bytes)).unwrap_err(),
+        ] {
+            assert!(matches!(error.kind(), ErrorKind::ChildOutOfBounds { .. }));
+        }
+
+        // Past the end of the file
+        bytes[offset..offset + 4].copy_from_slice(&0x100u32.to_le_bytes());
+        assert!(matches!(
+            from_slice::<Empty>(&bytes).unwrap_err().kind(),
+            ErrorKind::ChildOutOfBounds { .. }
+        ));
+    }
+}
+
+#[cfg(feature = "rayon")]
+mod parallel {
+    use crate::{from_slice, par_list_from_slice, par_value_from_slice, to_vec, ParamFile, Value};
+    use hash40::hash40;
+    use serde::{Deserialize, Serialize};
+    use serial_test::serial;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Entry {
+        name: String,
+        slot: u8,
+        tags: Vec<i32>,
+    }
+
+    fn entries() -> Vec<Entry> {
+        (0..500)
+            .map(|i| Entry {
+                name: format!("entry_{}", i % 32),
+                slot: i as u8,
+                tags: (0..i % 5).collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    #[serial]
+    fn values() {
+        let mut root = BTreeMap::new();
+        root.insert("entries", entries());
+        let bytes = to_vec(&root).unwrap();
+
+        assert_eq!(
+            par_value_from_slice(&bytes).unwrap(),
+            from_slice::<Value>(&bytes).unwrap()
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn root_list() {
+        let bytes = to_vec(&entries()).unwrap();
+        assert_eq!(par_list_from_slice::<Entry>(&bytes).unwrap(), entries());
+    }
+
+    #[test]
+    #[serial]
+    fn nested_list() {
+        let mut root = BTreeMap::new();
+        root.insert("entries", entries());
+        let bytes = to_vec(&root).unwrap();
+
+        let file = ParamFile::from_slice(&bytes).unwrap();
+        let list = file.get(hash40("entries")).unwrap();
+        assert_eq!(list.par_elements::<Entry>().unwrap(), entries());
+        assert!(file.root().unwrap().par_elements::<Entry>().is_err());
+    }
+}
+
+#[cfg(feature = "xml")]
+mod xml {
+    use crate::{
+        xml::{self, ErrorKind},
+        Value,
+    };
+    use hash40::{hash40, Hash40};
+    use indexmap::IndexMap;
+    use serial_test::serial;
+
+    fn value() -> Value {
+        let mut inner = IndexMap::new();
+        inner.insert(hash40("flag"), Value::Bool(true));
+        inner.insert(hash40("empty_string"), Value::String(String::new()));
+        inner.insert(hash40("empty_list"), Value::List(vec![]));
+        inner.insert(hash40("empty_struct"), Value::Map(IndexMap::new()));
+
+        let mut root = IndexMap::new();
+        root.insert(hash40("i8"), Value::I8(-128));
+        root.insert(hash40("u8"), Value::U8(255));
+        root.insert(hash40("i16"), Value::I16(-300));
+        root.insert(hash40("u16"), Value::U16(60000));
+        root.insert(hash40("i32"), Value::I32(-70000));
+        root.insert(hash40("u32"), Value::U32(4_000_000_000));
+        root.insert(hash40("f32"), Value::F32(0.1));
+        root.insert(hash40("hash"), Value::Hash(hash40("fighter_kind_mario")));
+        root.insert(hash40("text"), Value::String("<a & \"b\">".to_string()));
+        root.insert(
+            hash40("list"),
+            Value::List(vec![Value::Map(inner), Value::F32(-0.0), Value::U8(0)]),
+        );
+        Value::Map(root)
+    }
+
+    #[test]
+    #[serial]
+    fn round_trip() {
+        Hash40::label_map().lock().unwrap().clear();
+        let text = xml::to_string(&value());
+        assert_eq!(xml::from_str(&text).unwrap(), value());
+
+        // Empty strings, lists and structs are written as self-closing elements
+        for (name, key) in [
+            ("string", "empty_string"),
+            ("list", "empty_list"),
+            ("struct", "empty_struct"),
+        ] {
+            let element = format!("<{name} hash=\"{}\" />", hash40(key));
+            assert!(text.contains(&element), "{element}");
+        }
+
+        let mut bytes = Vec::new();
+        xml::to_writer(&mut bytes, &value()).unwrap();
+        assert_eq!(bytes, text.as_bytes());
+    }
+
+    #[test]
+    #[serial]
+    fn layout() {
+        Hash40::label_map().lock().unwrap().clear();
+        Hash40::label_map()
+            .lock()
+            .unwrap()
+            .add_labels(vec!["slots".to_string(), "fighter_kind_mario".to_string()]);
+
+        let mut root = IndexMap::new();
+        root.insert(
+            hash40("slots"),
+            Value::List(vec![
+                Value::I8(-1),
+                Value::Hash(hash40("fighter_kind_mario")),
+            ]),
+        );
+        root.insert(Hash40(0x0123456789), Value::Bool(false));
+        let text = xml::to_string(&Value::Map(root));
+
+        Hash40::label_map().lock().unwrap().clear();
+
+        assert_eq!(
+            text,
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<struct>
+  <list hash="slots">
+    <sbyte index="0">-1</sbyte>
+    <hash40 index="1">fighter_kind_mario</hash40>
+  </list>
+  <bool hash="0x0123456789">False</bool>
+</struct>
+"#
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn error_positions() {
+        let error = xml::from_str(
+            "<struct>\n  <float hash=\"a\">1.5</float>\n  <float hash=\"b\">x</float>\n</struct>",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::InvalidValue { element: "float", text } if text == "x"
+        ));
+        assert_eq!((error.line(), error.column()), (3, 3));
+
+        let error =
+            xml::from_str("<struct>\n    <double hash=\"a\">1</double>\n</struct>").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::UnknownElement(name) if name == "double"));
+        assert_eq!((error.line(), error.column()), (2, 5));
+
+        let error = xml::from_str("<struct>\n  <int>1</int>\n</struct>").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::MissingHash("int")));
+        assert_eq!((error.line(), error.column()), (2, 3));
+
+        let error = xml::from_str("<list>\n  <int index=\"1\">1</int>\n</list>").unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::InvalidIndex { expected: 0, .. }
+        ));
+
+        let error = xml::from_str("<struct>\n  <int hash=\"a\">1</uint>\n</struct>").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Xml(_)));
+        assert_eq!(error.line(), 2);
+
+        let error = xml::from_str("<struct>\n  <int hash=\"a\">1</int>\n").unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::UnexpectedEof | ErrorKind::Xml(_)
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn strict_labels() {
+        Hash40::label_map().lock().unwrap().clear();
+        Hash40::label_map().lock().unwrap().strict = true;
+        let result = xml::from_str("<struct><int hash=\"not_a_label\">1</int></struct>");
+        Hash40::label_map().lock().unwrap().strict = false;
+
+        assert!(matches!(
+            result.unwrap_err().kind(),
+            ErrorKind::UnknownLabel(label) if label == "not_a_label"
+        ));
+    }
+}
+
+mod tagged {
+    use crate::{
+        from_slice,
+        tagged::{Tagged, TaggedRef},
+        to_vec, Value,
+    };
+    use hash40::{hash40, Hash40};
+    use indexmap::IndexMap;
+    use serde::{Deserialize, Serialize};
+    use serial_test::serial;
+
+    /// Params which plain JSON reads back as a different type: the limits of every integer
+    /// width, a whole float, a small hash and a string which looks like a hash
+    fn value() -> Value {
+        let mut inner = IndexMap::new();
+        inner.insert(hash40("small_hash"), Value::Hash(Hash40(1)));
+        inner.insert(hash40("whole_float"), Value::F32(1.0));
+        inner.insert(
+            hash40("hash_string"),
+            Value::String("0x0123456789".to_string()),
+        );
+        inner.insert(hash40("empty"), Value::List(vec![]));
+
+        let mut root = IndexMap::new();
+        root.insert(hash40("flag"), Value::Bool(true));
+        root.insert(hash40("i8"), Value::I8(i8::MIN));
+        root.insert(hash40("u8"), Value::U8(u8::MAX));
+        root.insert(hash40("i16"), Value::I16(i16::MIN));
+        root.insert(hash40("u16"), Value::U16(u16::MAX));
+        root.insert(hash40("i32"), Value::I32(i32::MIN));
+        root.insert(hash40("u32"), Value::U32(u32::MAX));
+        root.insert(hash40("hash"), Value::Hash(Hash40(0x0123456789)));
+        root.insert(
+            hash40("list"),
+            Value::List(vec![Value::Map(inner), Value::U16(0), Value::I8(0)]),
+        );
+        Value::Map(root)
+    }
+
+    #[test]
+    #[serial]
+    fn json_round_trip() {
+        Hash40::label_map().lock().unwrap().clear();
+        let bytes = to_vec(&value()).unwrap();
+        let value: Value = from_slice(&bytes).unwrap();
+
+        let json = serde_json::to_string(&TaggedRef(&value)).unwrap();
+        let Tagged(back) = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, value);
+        assert_eq!(to_vec(&back).unwrap(), bytes);
+
+        // Without the tags, the widths are lost
+        let untagged: Value =
+            serde_json::from_str(&serde_json::to_string(&value).unwrap()).unwrap();
+        assert_ne!(untagged, value);
+    }
+
+    #[test]
+    #[serial]
+    fn json_shape() {
+        Hash40::label_map().lock().unwrap().clear();
+        let mut map = IndexMap::new();
+        map.insert(Hash40(0x0123456789), Value::Hash(Hash40(0x0a0b0c0d0e)));
+        map.insert(Hash40(0x0000000001), Value::F32(0.5));
+
+        assert_eq!(
+            serde_json::to_value(TaggedRef(&Value::Map(map))).unwrap(),
+            serde_json::json!({
+                "map": {
+                    "0x0123456789": { "hash": "0x0a0b0c0d0e" },
+                    "0x0000000001": { "f32": 0.5 },
+                }
+            })
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn unknown_tag() {
+        let error = serde_json::from_str::<Tagged>(r#"{"f64": 1.0}"#).unwrap_err();
+        assert!(error.to_string().contains("unknown variant `f64`"));
+
+        let error = serde_json::from_str::<Tagged>(r#"{"u8": 300}"#).unwrap_err();
+        assert!(error.to_string().contains("300"));
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Patch {
+        target: String,
+        #[serde(with = "crate::tagged")]
+        value: Value,
+    }
+
+    #[test]
+    #[serial]
+    fn with_module() {
+        let patch = Patch {
+            target: "fighter_param".to_string(),
+            value: Value::List(vec![Value::I16(4), Value::U32(5)]),
+        };
+
+        let json = serde_json::to_string(&patch).unwrap();
+        assert_eq!(
+            json,
+            r#"{"target":"fighter_param","value":{"list":[{"i16":4},{"u32":5}]}}"#
+        );
+        assert_eq!(serde_json::from_str::<Patch>(&json).unwrap(), patch);
+    }
+
+    #[test]
+    #[serial]
+    fn non_human_readable() {
+        Hash40::label_map().lock().unwrap().clear();
+        let bytes = to_vec(&TaggedRef(&value())).unwrap();
+        let Tagged(back) = from_slice(&bytes).unwrap();
+        assert_eq!(back, value());
+    }
+}
+
+mod text {
+    use crate::{
+        from_slice,
+        text::{self, ErrorKind},
+        to_vec, Value,
+    };
+    use hash40::{hash40, Hash40};
+    use indexmap::IndexMap;
+    use serial_test::serial;
+
+    /// Floats which only survive exact formatting, the limits of every integer width, a reserved
+    /// word as a key, unlabeled hashes and strings with quotes and control characters
+    fn value() -> Value {
+        let floats = [
+            0.1,
+            -0.0,
+            1e-10,
+            f32::MAX,
+            f32::MIN_POSITIVE / 4.0,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NAN,
+        ];
+
+        let mut inner = IndexMap::new();
+        inner.insert(hash40("empty_list"), Value::List(vec![]));
+        inner.insert(hash40("empty_map"), Value::Map(IndexMap::new()));
+        inner.insert(
+            hash40("text"),
+            Value::String("quote \" slash \\ tab \t bell \x07".to_string()),
+        );
+        inner.insert(hash40("unlabeled"), Value::Hash(Hash40(0x0a0b0c0d0e)));
+
+        let mut root = IndexMap::new();
+        root.insert(hash40("flag"), Value::Bool(false));
+        root.insert(hash40("i8"), Value::I8(-128));
+        root.insert(hash40("u8"), Value::U8(255));
+        root.insert(hash40("i16"), Value::I16(-32768));
+        root.insert(hash40("u16"), Value::U16(65535));
+        root.insert(hash40("i32"), Value::I32(i32::MIN));
+        root.insert(hash40("u32"), Value::U32(u32::MAX));
+        root.insert(
+            hash40("floats"),
+            Value::List(floats.into_iter().map(Value::F32).collect()),
+        );
+        root.insert(hash40("true"), Value::Hash(hash40("fighter_kind_mario")));
+        root.insert(Hash40(0x0123456789), Value::Map(inner));
+        Value::Map(root)
+    }
+
+    #[test]
+    #[serial]
+    fn lossless() {
+        Hash40::label_map().lock().unwrap().clear();
+        Hash40::label_map().lock().unwrap().add_labels(vec![
+            "flag".to_string(),
+            "floats".to_string(),
+            "true".to_string(),
+            "fighter_kind_mario".to_string(),
+        ]);
+
+        let bytes = to_vec(&value()).unwrap();
+        let pretty = text::to_string_pretty(&from_slice(&bytes).unwrap());
+        let compact = text::to_string(&value());
+
+        let from_pretty = text::from_str(&pretty).unwrap();
+        let from_compact = text::from_str(&compact).unwrap();
+        Hash40::label_map().lock().unwrap().clear();
+
+        assert_eq!(to_vec(&from_pretty).unwrap(), bytes);
+        assert_eq!(to_vec(&from_compact).unwrap(), bytes);
+
+        // Reserved words are never used as labels
+        assert!(pretty.contains(&format!("{:#012x}: fighter_kind_mario,", hash40("true").0)));
+        assert!(pretty.contains("    flag: false,"));
+    }
+
+    #[test]
+    #[serial]
+    fn layout() {
+        Hash40::label_map().lock().unwrap().clear();
+        Hash40::label_map()
+            .lock()
+            .unwrap()
+            .add_labels(vec!["slots".to_string(), "scale".to_string()]);
+
+        let mut root = IndexMap::new();
+        root.insert(
+            hash40("slots"),
+            Value::List(vec![Value::U8(1), Value::I32(-2)]),
+        );
+        root.insert(hash40("scale"), Value::F32(1.0));
+        root.insert(Hash40(0x0a0b0c0d0e), Value::List(vec![]));
+        root.insert(hash40("slots2"), Value::String("a".to_string()));
+        let value = Value::Map(root);
+
+        let pretty = text::to_string_pretty(&value);
+        let compact = text::to_string(&value);
+        Hash40::label_map().lock().unwrap().clear();
+
+        assert_eq!(
+            pretty,
+            r#"{
+    slots: [
+        1u8,
+        -2,
+    ],
+    scale: 1.0f,
+    0x0a0b0c0d0e: [],
+    0x069cce18ec: "a",
+}
+"#
+        );
+        assert_eq!(
+            compact,
+            r#"{slots: [1u8, -2], scale: 1.0f, 0x0a0b0c0d0e: [], 0x069cce18ec: "a"}"#
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn syntax() {
+        Hash40::label_map().lock().unwrap().clear();
+        let value = text::from_str(
+            r#"
+            // A comment
+            {
+                a: [1, 2u16, -3i8, 4.5f, 1e3f, -inff, NaNf,], // trailing comma
+                b: { c: "\u{41}\n", d: true },
+                0x0123456789: label_hash,
+            }"#,
+        )
+        .unwrap();
+
+        let map = value.as_map().unwrap();
+        let list = map[&hash40("a")].as_list().unwrap();
+        assert_eq!(list[0], Value::I32(1));
+        assert_eq!(list[1], Value::U16(2));
+        assert_eq!(list[2], Value::I8(-3));
+        assert_eq!(list[3], Value::F32(4.5));
+        assert_eq!(list[4], Value::F32(1000.0));
+        assert_eq!(list[5], Value::F32(f32::NEG_INFINITY));
+        assert!(matches!(list[6], Value::F32(v) if v.is_nan()));
+
+        let b = map[&hash40("b")].as_map().unwrap();
+        assert_eq!(b[&hash40("c")], Value::String("A\n".to_string()));
+        assert_eq!(b[&hash40("d")], Value::Bool(true));
+        assert_eq!(
+            map[&Hash40(0x0123456789)],
+            Value::Hash(hash40("label_hash"))
+        );
+    }
+
+    fn parse_error(text: &str) -> text::Error {
+        text::from_str(text).unwrap_err()
+    }
+
+    #[test]
+    #[serial]
+    fn nan_payloads() {
+        let nans = [0x7fc00001, 0xffc00000, 0x7f800001].map(f32::from_bits);
+        let value = Value::List(nans.into_iter().map(Value::F32).collect());
+
+        let text = text::to_string(&value);
+        assert_eq!(
+            text,
+            "[NaNf(0x7fc00001), NaNf(0xffc00000), NaNf(0x7f800001)]"
+        );
+
+        let bytes = to_vec(&value).unwrap();
+        let parsed = text::from_str(&text).unwrap();
+        assert_eq!(to_vec(&parsed).unwrap(), bytes);
+        assert_eq!(text::to_string(&from_slice(&bytes).unwrap()), text);
+
+        for source in ["[NaNf(0x3f800000)]", "[NaNf(0x7fc0)]", "[NaNf(0x7fc00001]"] {
+            let error = parse_error(source);
+            assert!(
+                matches!(error.kind(), ErrorKind::InvalidNumber(_)),
+                "{source}"
+            );
+            assert!(source[error.span()].starts_with("NaNf("));
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn hex_literals() {
+        assert_eq!(
+            text::from_str("{0x0000000001: 0x0123456789}").unwrap(),
+            Value::Map([(Hash40(1), Value::Hash(Hash40(0x0123456789)))].into())
+        );
+
+        // Anything but ten digits isn't a hash, like with `hash_from_label`
+        for source in ["[0x1]", "{0x1: 1}", "[0x00000000001]"] {
+            let error = parse_error(source);
+            let literal = &source[error.span()];
+            assert!(matches!(error.kind(), ErrorKind::InvalidNumber(n) if n == literal));
+            assert_eq!(crate::hash_from_hex_literal(literal), None);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn error_spans() {
+        let source = "{\n    a: 300u8,\n}";
+        let error = parse_error(source);
+        assert!(matches!(error.kind(), ErrorKind::InvalidNumber(n) if n == "300"));
+        assert_eq!(&source[error.span()], "300u8");
+        assert_eq!((error.line(), error.column()), (2, 8));
+
+        let source = "[1u9]";
+        let error = parse_error(source);
+        assert!(matches!(error.kind(), ErrorKind::InvalidSuffix(s) if s == "u9"));
+        assert_eq!(&source[error.span()], "u9");
+
+        let source = "[1.5]";
+        assert!(matches!(parse_error(source).kind(), ErrorKind::InvalidSuffix(s) if s.is_empty()));
+
+        let source = "{a 1}";
+        let error = parse_error(source);
+        assert!(matches!(error.kind(), ErrorKind::Expected("':'")));
+        assert_eq!(&source[error.span()], "1");
+
+        let source = "{a: 1,\n  a: 2}";
+        let error = parse_error(source);
+        assert!(matches!(error.kind(), ErrorKind::DuplicateKey(_)));
+        assert_eq!(&source[error.span()], "a");
+        assert_eq!((error.line(), error.column()), (2, 3));
+
+        let source = "[1 2]";
+        let error = parse_error(source);
+        assert!(matches!(error.kind(), ErrorKind::Expected("',' or ']'")));
+        assert_eq!(error.span(), 3..4);
+
+        let source = r#"["abc\q"]"#;
+        let error = parse_error(source);
+        assert!(matches!(error.kind(), ErrorKind::InvalidEscape(e) if e == "\\q"));
+        assert_eq!(&source[error.span()], "\\q");
+
+        assert!(matches!(
+            parse_error("[\"abc").kind(),
+            ErrorKind::UnexpectedEof
+        ));
+        assert!(matches!(
+            parse_error("[1,").kind(),
+            ErrorKind::UnexpectedEof
+        ));
+        assert!(matches!(
+            parse_error("[@]").kind(),
+            ErrorKind::UnexpectedChar('@')
+        ));
+
+        let source = "[] []";
+        let error = parse_error(source);
+        assert!(matches!(error.kind(), ErrorKind::TrailingCharacters));
+        assert_eq!(error.span(), 3..5);
+    }
+
+    #[test]
+    #[serial]
+    fn strict_labels() {
+        Hash40::label_map().lock().unwrap().clear();
+        Hash40::label_map().lock().unwrap().strict = true;
+        let source = "{x: not_a_label}";
+        let result = text::from_str(source);
+        Hash40::label_map().lock().unwrap().strict = false;
+
+        let error = result.unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::UnknownLabel(label) if label == "x"));
+        assert_eq!(error.span(), 1..2);
+    }
+}
+
+mod string_escapes {
+    use crate::{
+        de::{Strings, ValueVisitor},
+        from_slice,
+        ser::Escaped,
+        to_vec, Value,
+    };
+    use hash40::{hash40, Hash40};
+    use serde::de::DeserializeSeed;
+    use serial_test::serial;
+
+    fn value() -> Value {
+        Value::List(vec![
+            Value::String("0x0123456789".to_string()),
+            Value::String("\\starts with a backslash".to_string()),
+            Value::String("plain".to_string()),
+            Value::Hash(Hash40(0x0123456789)),
+        ])
+    }
+
+    #[test]
+    #[serial]
+    fn json_round_trip() {
+        Hash40::label_map().lock().unwrap().clear();
+        let json = serde_json::to_string(&Escaped(&value())).unwrap();
+        assert_eq!(
+            json,
+            r#"["\\0x0123456789","\\\\starts with a backslash","plain","0x0123456789"]"#
+        );
+
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let read = ValueVisitor::new()
+            .strings(Strings::Escaped)
+            .deserialize(&mut deserializer)
+            .unwrap();
+        assert_eq!(read, value());
+    }
+
+    #[test]
+    #[serial]
+    fn unescaped_by_default() {
+        Hash40::label_map().lock().unwrap().clear();
+        let json = serde_json::to_string(&value()).unwrap();
+        assert_eq!(
+            json,
+            r#"["0x0123456789","\\starts with a backslash","plain","0x0123456789"]"#
+        );
+
+        let old = r#"{"name": "\\foo", "id": "0x0123456789"}"#;
+        assert_eq!(
+            serde_json::from_str::<Value>(old).unwrap(),
+            Value::Map(
+                [
+                    (hash40("name"), Value::String("\\foo".to_string())),
+                    (hash40("id"), Value::Hash(Hash40(0x0123456789))),
+                ]
+                .into_iter()
+                .collect()
+            )
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn param_files_are_verbatim() {
+        let bytes = to_vec(&value()).unwrap();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), value());
+    }
+
+    #[test]
+    #[serial]
+    fn conventions() {
+        let json = r#"["0x0123456789", "\\0x0123456789", "\\\\x"]"#;
+        let visit = |strings| {
+            let mut deserializer = serde_json::Deserializer::from_str(json);
+            ValueVisitor::new()
+                .strings(strings)
+                .deserialize(&mut deserializer)
+                .unwrap()
+        };
+
+        let hash = Value::Hash(Hash40(0x0123456789));
+        let string = |s: &str| Value::String(s.to_string());
+        assert_eq!(
+            visit(Strings::Escaped),
+            Value::List(vec![hash.clone(), string("0x0123456789"), string("\\x")])
+        );
+        assert_eq!(
+            visit(Strings::Unescaped),
+            Value::List(vec![hash, string("\\0x0123456789"), string("\\\\x")])
+        );
+        assert_eq!(
+            visit(Strings::Verbatim),
+            Value::List(vec![
+                string("0x0123456789"),
+                string("\\0x0123456789"),
+                string("\\\\x")
+            ])
+        );
+    }
+}
+
+mod labels {
+    use crate::labels::{Conflict, Error, Labels};
+    use hash40::{hash40, Hash40};
+    use serial_test::serial;
+
+    #[test]
+    #[serial]
+    fn parse_csv() {
+        let csv = "0x0123456789,first\n\n  0x0123456789 , first \n0x0123456789,second\n0x0a00000000,first\n0x038c736521,foo\n";
+        let labels = Labels::parse_csv(csv).unwrap();
+
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels.label_of(Hash40(0x0123456789)), Some("first"));
+        assert_eq!(labels.hash_of("foo"), Some(hash40("foo")));
+        assert_eq!(
+            labels.conflicts(),
+            [
+                Conflict::Collision {
+                    hash: Hash40(0x0123456789),
+                    kept: "first".to_string(),
+                    dropped: "second".to_string(),
+                },
+                Conflict::DuplicateLabel {
+                    label: "first".to_string(),
+                    kept: Hash40(0x0123456789),
+                    dropped: Hash40(0x0a00000000),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn csv_errors() {
+        assert!(matches!(
+            Labels::parse_csv("0x0123456789,foo\nbar\n"),
+            Err(Error::MissingLabel { line: 2 })
+        ));
+        assert!(matches!(
+            Labels::parse_csv("0x0123456789,\n"),
+            Err(Error::MissingLabel { line: 1 })
+        ));
+        assert!(matches!(
+            Labels::parse_csv("foo,bar\n"),
+            Err(Error::InvalidHash { line: 1, .. })
+        ));
+        assert!(matches!(
+            Labels::parse_csv("0x10000000000,bar\n"),
+            Err(Error::InvalidHash { line: 1, .. })
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn merge_keeps_earlier_sources() {
+        let mut labels = Labels::parse_csv("0x038c736521,custom_foo\n").unwrap();
+        labels.merge(Labels::parse_list("foo\nbar\n"));
+
+        assert_eq!(labels.label_of(hash40("foo")), Some("custom_foo"));
+        assert_eq!(labels.label_of(hash40("bar")), Some("bar"));
+        assert_eq!(labels.hash_of("foo"), None);
+        assert_eq!(
+            labels.iter().collect::<Vec<_>>(),
+            [(hash40("foo"), "custom_foo"), (hash40("bar"), "bar")]
+        );
+        assert_eq!(labels.conflicts().len(), 1);
+    }
+
+    #[test]
+    #[serial]
+    fn install() {
+        Hash40::label_map().lock().unwrap().clear();
+        Hash40::label_map()
+            .lock()
+            .unwrap()
+            .add_labels(vec!["foo".to_string()]);
+
+        let mut labels = Labels::parse_csv("0x038c736521,custom_foo\n").unwrap();
+        labels.merge(Labels::parse_list("bar\n"));
+        labels.install();
+
+        assert_eq!(hash40("foo").to_label(), "custom_foo");
+        assert_eq!(hash40("bar").to_label(), "bar");
+        assert_eq!(Hash40::from_label("custom_foo").unwrap(), hash40("foo"));
+
+        Hash40::label_map().lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,570 @@
+//! A compact text syntax for [`Value`], meant to be read in diffs and code review.
+//!
+//! ```text
+//! {
+//!     // Comments run to the end of the line
+//!     fighter_kind: fighter_kind_mario,
+//!     jump_count: 2u8,
+//!     walk_speed: 1.05f,
+//!     weight: 98,
+//!     name: "mario",
+//!     unlabeled: 0x0123456789,
+//!     colors: [0u8, 1u8],
+//!     empty: {},
+//! }
+//! ```
+//!
+//! Integers carry their param type as a suffix (`i8`, `u8`, `i16`, `u16`, `i32` or `u32`) and
+//! default to `i32` without one. Floats always end in `f`. Hashes are written as bare labels or
+//! `0x0123456789` hex literals, which always have ten digits, and strings are double quoted.
+//! `true` and `false` are bools.
+//!
+//! Text goes through `Value` and the binary format without losing anything: every param keeps
+//! its type, floats are printed so they parse back to the same bits (NaNs other than the usual
+//! `NaNf` are written with their bits, such as `NaNf(0x7fc00001)`) and hashes are only shown as
+//! labels when the label parses back to the same hash.
+
+use std::{
+    fmt::{Display, Write},
+    ops::Range,
+};
+
+use hash40::Hash40;
+use indexmap::IndexMap;
+use thiserror::Error;
+
+use crate::Value;
+
+#[derive(Error, Debug)]
+pub enum ErrorKind {
+    #[error("Unexpected end of input")]
+    UnexpectedEof,
+
+    #[error("Unexpected character {0:?}")]
+    UnexpectedChar(char),
+
+    #[error("Expected {0}")]
+    Expected(&'static str),
+
+    #[error("Invalid number {0:?}")]
+    InvalidNumber(String),
+
+    #[error("Invalid number suffix {0:?}")]
+    InvalidSuffix(String),
+
+    #[error("Invalid escape sequence {0:?}")]
+    InvalidEscape(String),
+
+    #[error("Unknown label {0:?}")]
+    UnknownLabel(String),
+
+    #[error("Map has more than one value for {0}")]
+    DuplicateKey(Hash40),
+
+    #[error("Unexpected characters after the value")]
+    TrailingCharacters,
+}
+
+/// An error parsing the text format, along with the span of the input it applies to
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    span: Range<usize>,
+    line: usize,
+    column: usize,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    fn new(kind: ErrorKind, text: &str, span: Range<usize>) -> Self {
+        let before = &text[..span.start];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+        Self {
+            kind,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            span,
+        }
+    }
+
+    /// The underlying cause of this error, without the position information
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The byte range of the input the error applies to
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// The line the error starts on, starting from 1
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column the error starts at, in characters and starting from 1
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// Words which can't be used as bare labels
+const RESERVED: &[&str] = &["true", "false", "NaNf", "inff"];
+
+fn is_label_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_label_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/// Formats `value` on a single line
+pub fn to_string(value: &Value) -> String {
+    let mut out = String::new();
+    // Writing to a string can't fail
+    let _ = write_value(&mut out, value, None);
+    out
+}
+
+/// Formats `value` with one entry per line, using labels from the global label map
+pub fn to_string_pretty(value: &Value) -> String {
+    let mut out = String::new();
+    let _ = write_value(&mut out, value, Some(0));
+    out.push('\n');
+    out
+}
+
+fn write_hash(out: &mut String, hash: Hash40) -> std::fmt::Result {
+    let label = format!("{hash}");
+    let bare = label.starts_with(is_label_start)
+        && label.chars().all(is_label_char)
+        && !RESERVED.contains(&label.as_str())
+        && crate::hash_from_label(&label) == Some(hash);
+
+    if bare {
+        out.write_str(&label)
+    } else {
+        write!(out, "{:#012x}", hash.0)
+    }
+}
+
+/// Writes `value`, either on one line or indented `depth` levels deep
+fn write_value(out: &mut String, value: &Value, depth: Option<usize>) -> std::fmt::Result {
+    match value {
+        Value::Bool(v) => write!(out, "{v}"),
+        Value::I8(v) => write!(out, "{v}i8"),
+        Value::U8(v) => write!(out, "{v}u8"),
+        Value::I16(v) => write!(out, "{v}i16"),
+        Value::U16(v) => write!(out, "{v}u16"),
+        Value::I32(v) => write!(out, "{v}"),
+        Value::U32(v) => write!(out, "{v}u32"),
+        // Only one NaN has a name, any other sign or payload has to be spelled out
+        Value::F32(v) if v.is_nan() && v.to_bits() != f32::NAN.to_bits() => {
+            write!(out, "NaNf({:#010x})", v.to_bits())
+        }
+        // Debug formatting always parses back to the same value
+        Value::F32(v) => write!(out, "{v:?}f"),
+        Value::Hash(v) => write_hash(out, *v),
+        Value::String(v) => write!(out, "{v:?}"),
+        Value::List(list) => write_container(out, "[]", list.iter(), depth, |out, value, depth| {
+            write_value(out, value, depth)
+        }),
+        Value::Map(map) => {
+            write_container(out, "{}", map.iter(), depth, |out, (key, value), depth| {
+                write_hash(out, *key)?;
+                out.write_str(": ")?;
+                write_value(out, value, depth)
+            })
+        }
+    }
+}
+
+/// Writes `items` between the two characters of `delimiters`, separated by commas
+fn write_container<T>(
+    out: &mut String,
+    delimiters: &str,
+    items: impl ExactSizeIterator<Item = T>,
+    depth: Option<usize>,
+    mut write_item: impl FnMut(&mut String, T, Option<usize>) -> std::fmt::Result,
+) -> std::fmt::Result {
+    let (open, close) = delimiters.split_at(1);
+    out.write_str(open)?;
+
+    match depth {
+        Some(depth) if items.len() > 0 => {
+            for item in items {
+                write!(out, "\n{:indent$}", "", indent = (depth + 1) * 4)?;
+                write_item(out, item, Some(depth + 1))?;
+                out.write_char(',')?;
+            }
+            write!(out, "\n{:indent$}", "", indent = depth * 4)?;
+        }
+        _ => {
+            for (index, item) in items.enumerate() {
+                if index > 0 {
+                    out.write_str(", ")?;
+                }
+                write_item(out, item, None)?;
+            }
+        }
+    }
+
+    out.write_str(close)
+}
+
+/// Parses a value written in the text format
+pub fn from_str(text: &str) -> Result<Value, Error> {
+    let mut parser = Parser { text, position: 0 };
+    let value = parser.value()?;
+
+    parser.skip_whitespace();
+    if parser.position < text.len() {
+        return Err(parser.error(ErrorKind::TrailingCharacters, parser.position..text.len()));
+    }
+
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, kind: ErrorKind, span: Range<usize>) -> Error {
+        Error::new(kind, self.text, span)
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// An error for whatever comes next, expected or not
+    fn unexpected(&self) -> Error {
+        match self.peek() {
+            Some(c) => self.error(
+                ErrorKind::UnexpectedChar(c),
+                self.position..self.position + c.len_utf8(),
+            ),
+            None => self.error(ErrorKind::UnexpectedEof, self.position..self.position),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.position += rest.len() - trimmed.len();
+
+            if !trimmed.starts_with("//") {
+                return;
+            }
+
+            self.position += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    /// Consumes characters while `f` holds, returning them
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let len = rest.find(|c| !f(c)).unwrap_or(rest.len());
+        self.position += len;
+        &rest[..len]
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.expected(expected))
+        }
+    }
+
+    fn expected(&self, expected: &'static str) -> Error {
+        let end = self.position + self.peek().map_or(0, char::len_utf8);
+        self.error(ErrorKind::Expected(expected), self.position..end)
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.map(),
+            Some('[') => self.list(),
+            Some('"') => self.string().map(Value::String),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) if is_label_start(c) => {
+                let start = self.position;
+                match self.take_while(is_label_char) {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "NaNf" if self.peek() == Some('(') => self.nan_bits(start).map(Value::F32),
+                    "NaNf" => Ok(Value::F32(f32::NAN)),
+                    "inff" => Ok(Value::F32(f32::INFINITY)),
+                    label => self.label(label, start).map(Value::Hash),
+                }
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn label(&self, label: &str, start: usize) -> Result<Hash40, Error> {
+        crate::hash_from_label(label).ok_or_else(|| {
+            self.error(
+                ErrorKind::UnknownLabel(label.to_string()),
+                start..self.position,
+            )
+        })
+    }
+
+    /// The `(0x7fc00001)` after a `NaNf` which isn't the usual NaN, with the position at the
+    /// `(`
+    fn nan_bits(&mut self, start: usize) -> Result<f32, Error> {
+        self.position += 1;
+        let bits = self.take_while(|c| c.is_ascii_alphanumeric());
+        let nan = bits
+            .strip_prefix("0x")
+            .filter(|digits| digits.len() == 8)
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .map(f32::from_bits)
+            .filter(|v| v.is_nan());
+
+        if self.peek() == Some(')') {
+            self.position += 1;
+        }
+
+        let literal = &self.text[start..self.position];
+        match nan {
+            Some(nan) if literal.ends_with(')') => Ok(nan),
+            _ => Err(self.error(
+                ErrorKind::InvalidNumber(literal.to_string()),
+                start..self.position,
+            )),
+        }
+    }
+
+    /// A hex literal with exactly ten digits, the same as [`crate::hash_from_label`] accepts,
+    /// with the position at the `0x`
+    fn hex(&mut self) -> Result<Hash40, Error> {
+        let start = self.position;
+        self.position += 2;
+        self.take_while(|c| c.is_ascii_alphanumeric());
+
+        let literal = &self.text[start..self.position];
+        crate::hash_from_hex_literal(literal).ok_or_else(|| {
+            self.error(
+                ErrorKind::InvalidNumber(literal.to_string()),
+                start..self.position,
+            )
+        })
+    }
+
+    fn key(&mut self) -> Result<Hash40, Error> {
+        self.skip_whitespace();
+        let start = self.position;
+        match self.peek() {
+            Some(c) if is_label_start(c) => {
+                let label = self.take_while(is_label_char);
+                self.label(label, start)
+            }
+            Some('0') if self.rest().starts_with("0x") => self.hex(),
+            _ => Err(self.expected("a label or hex hash")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, Error> {
+        let start = self.position;
+        if self.rest().starts_with("0x") {
+            return self.hex().map(Value::Hash);
+        }
+
+        if self.rest().starts_with("-inff") {
+            self.position += 5;
+            return Ok(Value::F32(f32::NEG_INFINITY));
+        }
+
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+        self.take_while(|c| c.is_ascii_digit());
+
+        let mut is_float = false;
+        if self.rest().starts_with('.') {
+            is_float = true;
+            self.position += 1;
+            self.take_while(|c| c.is_ascii_digit());
+        }
+        if self.rest().starts_with(['e', 'E']) {
+            is_float = true;
+            self.position += 1;
+            if self.rest().starts_with(['-', '+']) {
+                self.position += 1;
+            }
+            self.take_while(|c| c.is_ascii_digit());
+        }
+
+        let digits = &self.text[start..self.position];
+        let suffix_start = self.position;
+        let suffix = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let span = start..self.position;
+
+        let invalid = || self.error(ErrorKind::InvalidNumber(digits.to_string()), span.clone());
+        let value = match suffix {
+            "f" => Value::F32(digits.parse().map_err(|_| invalid())?),
+            _ if is_float => {
+                return Err(self.error(
+                    ErrorKind::InvalidSuffix(suffix.to_string()),
+                    suffix_start..self.position,
+                ))
+            }
+            "" | "i32" => Value::I32(digits.parse().map_err(|_| invalid())?),
+            "i8" => Value::I8(digits.parse().map_err(|_| invalid())?),
+            "u8" => Value::U8(digits.parse().map_err(|_| invalid())?),
+            "i16" => Value::I16(digits.parse().map_err(|_| invalid())?),
+            "u16" => Value::U16(digits.parse().map_err(|_| invalid())?),
+            "u32" => Value::U32(digits.parse().map_err(|_| invalid())?),
+            _ => {
+                return Err(self.error(
+                    ErrorKind::InvalidSuffix(suffix.to_string()),
+                    suffix_start..self.position,
+                ))
+            }
+        };
+
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let start = self.position;
+        self.position += 1;
+
+        let mut string = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error(ErrorKind::UnexpectedEof, start..self.position));
+            };
+            let escape_start = self.position;
+            self.position += c.len_utf8();
+
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escaped = match self.peek() {
+                        Some(escape) => {
+                            self.position += escape.len_utf8();
+                            match escape {
+                                'n' => Some('\n'),
+                                'r' => Some('\r'),
+                                't' => Some('\t'),
+                                '0' => Some('\0'),
+                                '\\' => Some('\\'),
+                                '"' => Some('"'),
+                                '\'' => Some('\''),
+                                'u' => self.unicode_escape(),
+                                _ => None,
+                            }
+                        }
+                        None => None,
+                    };
+
+                    let Some(escaped) = escaped else {
+                        return Err(self.error(
+                            ErrorKind::InvalidEscape(
+                                self.text[escape_start..self.position].to_string(),
+                            ),
+                            escape_start..self.position,
+                        ));
+                    };
+                    string.push(escaped);
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    /// The `{...}` part of a `\u{...}` escape
+    fn unicode_escape(&mut self) -> Option<char> {
+        let rest = self.rest().strip_prefix('{')?;
+        let end = rest.find('}')?;
+        let c = u32::from_str_radix(&rest[..end], 16)
+            .ok()
+            .and_then(char::from_u32)?;
+        self.position += end + 2;
+        Some(c)
+    }
+
+    /// Parses comma separated items up to `close`, allowing a trailing comma
+    fn items(
+        &mut self,
+        close: char,
+        expected: &'static str,
+        mut item: impl FnMut(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.position += 1;
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(close) {
+                self.position += 1;
+                return Ok(());
+            }
+
+            item(self)?;
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(c) if c == close => {}
+                _ => return Err(self.expected(expected)),
+            }
+        }
+    }
+
+    fn list(&mut self) -> Result<Value, Error> {
+        let mut list = Vec::new();
+        self.items(']', "',' or ']'", |parser| {
+            list.push(parser.value()?);
+            Ok(())
+        })?;
+
+        Ok(Value::List(list))
+    }
+
+    fn map(&mut self) -> Result<Value, Error> {
+        let mut map = IndexMap::new();
+        self.items('}', "',' or '}'", |parser| {
+            parser.skip_whitespace();
+            let start = parser.position;
+            let key = parser.key()?;
+            let end = parser.position;
+
+            parser.expect(':', "':'")?;
+            let value = parser.value()?;
+
+            if map.insert(key, value).is_some() {
+                return Err(parser.error(ErrorKind::DuplicateKey(key), start..end));
+            }
+
+            Ok(())
+        })?;
+
+        Ok(Value::Map(map))
+    }
+}
@@ -0,0 +1,447 @@
+//! Conversion between [`Value`] and the paramxml layout used by the modding community.
+//!
+//! Every param is an element named after its type (`<bool>`, `<sbyte>`, `<byte>`, `<short>`,
+//! `<ushort>`, `<int>`, `<uint>`, `<float>`, `<hash40>`, `<string>`, `<list>` and `<struct>`).
+//! Children of a struct carry their key in a `hash` attribute and children of a list carry their
+//! position in an `index` attribute. Hashes are written as labels when the global label map
+//! has one and as hex literals otherwise.
+//!
+//! ```
+//! # use serde_prc::Value;
+//! let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+//! <struct>
+//!   <float hash="scale">1.5</float>
+//!   <list hash="slots">
+//!     <byte index="0">3</byte>
+//!   </list>
+//! </struct>"#;
+//!
+//! let value = serde_prc::xml::from_str(xml).unwrap();
+//! assert_eq!(value.as_map().unwrap()[&hash40::hash40("scale")], Value::F32(1.5));
+//! assert_eq!(serde_prc::xml::from_str(&serde_prc::xml::to_string(&value)).unwrap(), value);
+//! ```
+
+use std::{
+    fmt::{Display, Write as _},
+    io::Write,
+};
+
+use hash40::Hash40;
+use indexmap::IndexMap;
+use quick_xml::{
+    escape::escape,
+    events::{BytesStart, Event},
+    Reader,
+};
+use thiserror::Error;
+
+use crate::{ParamId, Value};
+
+#[derive(Error, Debug)]
+pub enum ErrorKind {
+    #[error(transparent)]
+    Xml(#[from] quick_xml::Error),
+
+    #[error("Unknown element <{0}>")]
+    UnknownElement(String),
+
+    #[error("<{0}> inside of a struct has no hash attribute")]
+    MissingHash(&'static str),
+
+    #[error("Unknown label {0:?}")]
+    UnknownLabel(String),
+
+    #[error("Invalid <{element}> value {text:?}")]
+    InvalidValue { element: &'static str, text: String },
+
+    #[error("List element has index {found:?} (expected {expected})")]
+    InvalidIndex { expected: usize, found: String },
+
+    #[error("Struct has more than one value for {0}")]
+    DuplicateKey(Hash40),
+
+    #[error("Unexpected text inside of <{0}>")]
+    UnexpectedText(&'static str),
+
+    #[error("Unexpected element inside of <{0}>")]
+    UnexpectedElement(&'static str),
+
+    #[error("Document ends before the root element is closed")]
+    UnexpectedEof,
+
+    #[error("Document has no root element")]
+    MissingRoot,
+
+    #[error("Document has more than one root element")]
+    TrailingElement,
+}
+
+/// An error parsing paramxml, along with where in the document it happened
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    line: usize,
+    column: usize,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.kind, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    fn at(kind: impl Into<ErrorKind>, text: &str, offset: usize) -> Self {
+        let offset = offset.min(text.len());
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+        Self {
+            kind: kind.into(),
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// The underlying cause of this error, without the position information
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The line the error happened on, starting from 1
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The column the error happened at, in characters and starting from 1
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+fn element_name(id: ParamId) -> &'static str {
+    match id {
+        ParamId::Bool => "bool",
+        ParamId::I8 => "sbyte",
+        ParamId::U8 => "byte",
+        ParamId::I16 => "short",
+        ParamId::U16 => "ushort",
+        ParamId::I32 => "int",
+        ParamId::U32 => "uint",
+        ParamId::F32 => "float",
+        ParamId::Hash => "hash40",
+        ParamId::String => "string",
+        ParamId::List => "list",
+        ParamId::Map => "struct",
+    }
+}
+
+fn param_id(element: &str) -> Option<ParamId> {
+    Some(match element {
+        "bool" => ParamId::Bool,
+        "sbyte" => ParamId::I8,
+        "byte" => ParamId::U8,
+        "short" => ParamId::I16,
+        "ushort" => ParamId::U16,
+        "int" => ParamId::I32,
+        "uint" => ParamId::U32,
+        "float" => ParamId::F32,
+        "hash40" => ParamId::Hash,
+        "string" => ParamId::String,
+        "list" => ParamId::List,
+        "struct" => ParamId::Map,
+        _ => return None,
+    })
+}
+
+/// Writes `value` as a paramxml document
+pub fn to_writer<W: Write>(mut writer: W, value: &Value) -> std::io::Result<()> {
+    writer.write_all(to_string(value).as_bytes())
+}
+
+/// Formats `value` as a paramxml document
+pub fn to_string(value: &Value) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    write_param(&mut out, value, None, 0);
+    out
+}
+
+fn write_param(out: &mut String, value: &Value, attribute: Option<(&str, String)>, depth: usize) {
+    let name = element_name(value.param_id());
+    let _ = write!(out, "{:indent$}<{name}", "", indent = depth * 2);
+    if let Some((key, attribute)) = attribute {
+        let _ = write!(out, " {key}=\"{}\"", escape(&attribute));
+    }
+
+    let text = match value {
+        Value::Bool(v) => if *v { "True" } else { "False" }.to_string(),
+        Value::I8(v) => v.to_string(),
+        Value::U8(v) => v.to_string(),
+        Value::I16(v) => v.to_string(),
+        Value::U16(v) => v.to_string(),
+        Value::I32(v) => v.to_string(),
+        Value::U32(v) => v.to_string(),
+        Value::F32(v) => v.to_string(),
+        Value::Hash(v) => escape(format!("{v}")).into_owned(),
+        Value::String(v) => escape(v).into_owned(),
+        Value::List(list) if !list.is_empty() => {
+            out.push_str(">\n");
+            for (index, value) in list.iter().enumerate() {
+                write_param(out, value, Some(("index", index.to_string())), depth + 1);
+            }
+            let _ = writeln!(out, "{:indent$}</{name}>", "", indent = depth * 2);
+            return;
+        }
+        Value::Map(map) if !map.is_empty() => {
+            out.push_str(">\n");
+            for (key, value) in map.iter() {
+                write_param(out, value, Some(("hash", format!("{key}"))), depth + 1);
+            }
+            let _ = writeln!(out, "{:indent$}</{name}>", "", indent = depth * 2);
+            return;
+        }
+        Value::List(_) | Value::Map(_) => String::new(),
+    };
+
+    if text.is_empty() {
+        out.push_str(" />\n");
+    } else {
+        let _ = writeln!(out, ">{text}</{name}>");
+    }
+}
+
+/// An element which has been opened but not closed yet
+struct Frame {
+    id: ParamId,
+    /// Where the element starts, for errors about its contents
+    offset: usize,
+    hash: Option<String>,
+    index: Option<String>,
+    text: String,
+    value: Option<Value>,
+}
+
+impl Frame {
+    fn open(start: &BytesStart, text: &str, offset: usize) -> Result<Self, Error> {
+        let name = String::from_utf8_lossy(start.local_name().into_inner()).into_owned();
+        let Some(id) = param_id(&name) else {
+            return Err(Error::at(ErrorKind::UnknownElement(name), text, offset));
+        };
+
+        let mut frame = Self {
+            id,
+            offset,
+            hash: None,
+            index: None,
+            text: String::new(),
+            value: match id {
+                ParamId::List => Some(Value::List(Vec::new())),
+                ParamId::Map => Some(Value::Map(IndexMap::new())),
+                _ => None,
+            },
+        };
+
+        for attribute in start.attributes() {
+            let attribute = attribute
+                .map_err(|error| Error::at(quick_xml::Error::from(error), text, offset))?;
+            let value = attribute
+                .unescape_value()
+                .map_err(|error| Error::at(error, text, offset))?
+                .into_owned();
+
+            match attribute.key.local_name().into_inner() {
+                b"hash" => frame.hash = Some(value),
+                b"index" => frame.index = Some(value),
+                _ => {}
+            }
+        }
+
+        Ok(frame)
+    }
+
+    fn name(&self) -> &'static str {
+        element_name(self.id)
+    }
+
+    /// Finishes the element, parsing the text of leaf params
+    fn close(self, text: &str) -> Result<(Option<String>, Option<String>, Value), Error> {
+        let invalid = || {
+            Error::at(
+                ErrorKind::InvalidValue {
+                    element: self.name(),
+                    text: self.text.clone(),
+                },
+                text,
+                self.offset,
+            )
+        };
+
+        let trimmed = self.text.trim();
+        let value = match self.id {
+            ParamId::Bool => match trimmed.to_ascii_lowercase().as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => return Err(invalid()),
+            },
+            ParamId::I8 => Value::I8(trimmed.parse().map_err(|_| invalid())?),
+            ParamId::U8 => Value::U8(trimmed.parse().map_err(|_| invalid())?),
+            ParamId::I16 => Value::I16(trimmed.parse().map_err(|_| invalid())?),
+            ParamId::U16 => Value::U16(trimmed.parse().map_err(|_| invalid())?),
+            ParamId::I32 => Value::I32(trimmed.parse().map_err(|_| invalid())?),
+            ParamId::U32 => Value::U32(trimmed.parse().map_err(|_| invalid())?),
+            ParamId::F32 => Value::F32(trimmed.parse().map_err(|_| invalid())?),
+            ParamId::Hash => Value::Hash(parse_hash(trimmed, text, self.offset)?),
+            ParamId::String => Value::String(self.text),
+            ParamId::List | ParamId::Map => self.value.unwrap(),
+        };
+
+        Ok((self.hash, self.index, value))
+    }
+}
+
+fn parse_hash(label: &str, text: &str, offset: usize) -> Result<Hash40, Error> {
+    crate::hash_from_label(label)
+        .ok_or_else(|| Error::at(ErrorKind::UnknownLabel(label.to_string()), text, offset))
+}
+
+/// Parses a paramxml document
+pub fn from_str(text: &str) -> Result<Value, Error> {
+    let mut reader = Reader::from_str(text);
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut root = None;
+
+    loop {
+        // Point errors at the start of the markup rather than the whitespace before it
+        let position = reader.buffer_position() as usize;
+        let offset = position
+            + text
+                .get(position..)
+                .map_or(0, |rest| rest.len() - rest.trim_start().len());
+
+        let event = reader
+            .read_event()
+            .map_err(|error| Error::at(error, text, reader.error_position() as usize))?;
+
+        let (start, empty) = match event {
+            Event::Start(start) => (start, false),
+            Event::Empty(start) => (start, true),
+            Event::Text(contents) => {
+                let contents = contents
+                    .unescape()
+                    .map_err(|error| Error::at(error, text, offset))?;
+                match stack.last_mut() {
+                    Some(frame) if frame.value.is_none() => frame.text.push_str(&contents),
+                    Some(frame) if !contents.trim().is_empty() => {
+                        return Err(Error::at(
+                            ErrorKind::UnexpectedText(frame.name()),
+                            text,
+                            offset,
+                        ));
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            Event::CData(contents) => {
+                match stack.last_mut() {
+                    Some(frame) if frame.value.is_none() => {
+                        frame.text.push_str(&String::from_utf8_lossy(&contents))
+                    }
+                    Some(frame) => {
+                        return Err(Error::at(
+                            ErrorKind::UnexpectedText(frame.name()),
+                            text,
+                            offset,
+                        ));
+                    }
+                    None => {}
+                }
+                continue;
+            }
+            Event::End(_) => {
+                // quick-xml already checks that end tags match their start tags
+                let frame = stack.pop().unwrap();
+                close(frame, &mut stack, &mut root, text)?;
+                continue;
+            }
+            Event::Eof => break,
+            Event::Decl(_) | Event::PI(_) | Event::DocType(_) | Event::Comment(_) => continue,
+        };
+
+        if root.is_some() {
+            return Err(Error::at(ErrorKind::TrailingElement, text, offset));
+        }
+
+        if let Some(parent) = stack.last() {
+            if parent.value.is_none() {
+                return Err(Error::at(
+                    ErrorKind::UnexpectedElement(parent.name()),
+                    text,
+                    offset,
+                ));
+            }
+        }
+
+        let frame = Frame::open(&start, text, offset)?;
+        if empty {
+            close(frame, &mut stack, &mut root, text)?;
+        } else {
+            stack.push(frame);
+        }
+    }
+
+    if !stack.is_empty() {
+        return Err(Error::at(ErrorKind::UnexpectedEof, text, text.len()));
+    }
+
+    root.ok_or_else(|| Error::at(ErrorKind::MissingRoot, text, text.len()))
+}
+
+/// Closes `frame` and adds its value to the enclosing list or struct, or makes it the root
+fn close(
+    frame: Frame,
+    stack: &mut [Frame],
+    root: &mut Option<Value>,
+    text: &str,
+) -> Result<(), Error> {
+    let name = frame.name();
+    let start = frame.offset;
+    let (hash, index, value) = frame.close(text)?;
+
+    match stack.last_mut().and_then(|parent| parent.value.as_mut()) {
+        Some(Value::List(list)) => {
+            if let Some(index) = index {
+                if index.trim().parse::<usize>().ok() != Some(list.len()) {
+                    return Err(Error::at(
+                        ErrorKind::InvalidIndex {
+                            expected: list.len(),
+                            found: index,
+                        },
+                        text,
+                        start,
+                    ));
+                }
+            }
+            list.push(value);
+        }
+        Some(Value::Map(map)) => {
+            let Some(hash) = hash else {
+                return Err(Error::at(ErrorKind::MissingHash(name), text, start));
+            };
+
+            let key = parse_hash(&hash, text, start)?;
+            if map.insert(key, value).is_some() {
+                return Err(Error::at(ErrorKind::DuplicateKey(key), text, start));
+            }
+        }
+        _ => *root = Some(value),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,225 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+use hash40::hash40;
+use indexmap::IndexMap;
+use serde_prc::{Hash40, Value};
+
+/// Two entries with a few params of different types
+fn value() -> Value {
+    let entries = (0..2).map(|index| {
+        let mut map = IndexMap::new();
+        map.insert(hash40("id"), Value::Hash(Hash40(index)));
+        map.insert(hash40("scale"), Value::F32(index as f32 * 0.5));
+        map.insert(hash40("name"), Value::String(format!("entry_{index}")));
+        map.insert(
+            hash40("points"),
+            Value::List((0..4).map(Value::U8).collect()),
+        );
+        Value::Map(map)
+    });
+    Value::List(entries.collect())
+}
+
+/// The map of the second entry, for changing it
+fn second(value: &mut Value) -> &mut IndexMap<Hash40, Value> {
+    let Value::List(list) = value else {
+        unreachable!()
+    };
+    let Value::Map(map) = &mut list[1] else {
+        unreachable!()
+    };
+    map
+}
+
+/// A path in a directory of its own for each test, since tests run in parallel
+fn path(test: &str, name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("cli")
+        .join(test);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+fn write_param(test: &str, name: &str, value: &Value) -> PathBuf {
+    let path = path(test, name);
+    fs::write(&path, serde_prc::to_vec(value).unwrap()).unwrap();
+    path
+}
+
+fn read_param(path: &Path) -> Value {
+    serde_prc::from_slice(&fs::read(path).unwrap()).unwrap()
+}
+
+fn prc(args: &[&dyn AsRef<std::ffi::OsStr>]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_prc"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+#[test]
+fn get_and_set() {
+    let file = write_param("get_and_set", "entries.prc", &value());
+
+    let output = prc(&[&"get", &file, &"1/scale"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0.5f\n");
+
+    let output = prc(&[&"set", &file, &"/1/points/0", &"3u8"]);
+    assert!(output.status.success(), "{output:?}");
+
+    let mut value = read_param(&file);
+    assert_eq!(
+        second(&mut value)[&hash40("points")].as_list().unwrap()[0],
+        Value::U8(3)
+    );
+}
+
+#[test]
+fn set_keeps_param_types() {
+    let file = write_param("set_keeps_param_types", "entries.prc", &value());
+    let path = "1/points/0";
+
+    let output = prc(&[&"set", &file, &path, &"3"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(read_param(&file), value());
+
+    let output = prc(&[&"set", &file, &path, &"3", &"--force"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&prc(&[&"get", &file, &path])), "3\n");
+
+    let output = prc(&[&"get", &file, &"2"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn hex_keys() {
+    let mut value = value();
+    second(&mut value).insert(Hash40(1), Value::Bool(true));
+    let file = write_param("hex_keys", "entries.prc", &value);
+
+    let output = prc(&[&"get", &file, &"1/0x0000000001"]);
+    assert_eq!(stdout(&output), "true\n");
+
+    // Only full hex literals are hashes, anything shorter is a label
+    let output = prc(&[&"get", &file, &"1/0x1"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn diff() {
+    let a = write_param("diff", "a.prc", &value());
+
+    let mut changed = value();
+    let entry = second(&mut changed);
+    entry.insert(hash40("name"), Value::String("luigi".to_string()));
+    entry.insert(hash40("extra"), Value::Bool(true));
+    let b = write_param("diff", "b.prc", &changed);
+
+    let labels = path("diff", "labels.txt");
+    fs::write(&labels, "name\nextra\n").unwrap();
+
+    let output = prc(&[&"diff", &a, &b, &"--labels", &labels]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stdout(&output),
+        "~ /1/name: \"entry_1\" -> \"luigi\"\n+ /1/extra: true\n"
+    );
+
+    // The first labels file wins over the second one
+    let custom = path("diff", "custom.csv");
+    fs::write(
+        &custom,
+        format!("{:#012x},display_name\n", hash40("name").0),
+    )
+    .unwrap();
+
+    let output = prc(&[&"diff", &a, &b, &"-l", &custom, &"-l", &labels]);
+    assert_eq!(
+        stdout(&output),
+        "~ /1/display_name: \"entry_1\" -> \"luigi\"\n+ /1/extra: true\n"
+    );
+
+    let output = prc(&[&"diff", &a, &a]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn diff_labels_with_slashes() {
+    let key = hash40("a/b");
+    let a = write_param(
+        "diff_labels_with_slashes",
+        "a.prc",
+        &Value::Map([(key, Value::U8(1))].into_iter().collect()),
+    );
+    let b = write_param(
+        "diff_labels_with_slashes",
+        "b.prc",
+        &Value::Map([(hash40("c"), Value::U8(2))].into_iter().collect()),
+    );
+
+    let labels = path("diff_labels_with_slashes", "labels.txt");
+    fs::write(&labels, "a/b\nc\n").unwrap();
+
+    let output = prc(&[&"diff", &a, &b, &"--labels", &labels]);
+    assert_eq!(
+        stdout(&output),
+        format!("- /{:#012x}: 1u8\n+ /c: 2u8\n", key.0)
+    );
+
+    // The printed path can be used to address the param
+    let output = prc(&[
+        &"get",
+        &a,
+        &format!("/{:#012x}", key.0),
+        &"--labels",
+        &labels,
+    ]);
+    assert_eq!(stdout(&output), "1u8\n");
+}
+
+#[test]
+fn conversions_round_trip() {
+    let file = write_param("conversions_round_trip", "entries.prc", &value());
+
+    for format in ["xml", "json"] {
+        let text = path("conversions_round_trip", &format!("entries.{format}"));
+        let back = path("conversions_round_trip", &format!("{format}.prc"));
+
+        let output = prc(&[&format!("to-{format}"), &file, &"-o", &text]);
+        assert!(output.status.success(), "{output:?}");
+        let output = prc(&[&format!("from-{format}"), &text, &"-o", &back]);
+        assert!(output.status.success(), "{output:?}");
+
+        assert_eq!(fs::read(&back).unwrap(), fs::read(&file).unwrap());
+    }
+}
+
+#[test]
+fn validate() {
+    let good = write_param("validate", "good.prc", &value());
+    let bad = path("validate", "bad.prc");
+    let bytes = fs::read(&good).unwrap();
+    fs::write(&bad, &bytes[..bytes.len() - 4]).unwrap();
+
+    assert!(prc(&[&"validate", &good]).status.success());
+
+    let output = prc(&[&"validate", &good, &bad]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), format!("{}: ok\n", good.display()));
+
+    let stderr = std::str::from_utf8(&output.stderr).unwrap();
+    assert!(
+        stderr.starts_with(&format!("{}: ", bad.display())),
+        "{stderr}"
+    );
+}